@@ -0,0 +1,11 @@
+CREATE TABLE admin_audit (
+    id bigserial PRIMARY KEY,
+    discord_id bigint NOT NULL,
+    action text NOT NULL,
+    arguments text NOT NULL,
+    old_value text,
+    new_value text,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE INDEX admin_audit_created_at_idx ON admin_audit (created_at DESC);
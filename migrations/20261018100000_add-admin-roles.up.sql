@@ -0,0 +1,9 @@
+CREATE TABLE admin_roles (
+    discord_id bigint PRIMARY KEY,
+    role text NOT NULL,
+    granted_by bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE TRIGGER set_updated_timestamp BEFORE UPDATE ON admin_roles FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
@@ -0,0 +1,19 @@
+-- withdrawals above `withdrawal_approval_threshold` wait here until a treasurer approves or
+-- rejects them. The amount and the fee are taken from the balance when the withdrawal is
+-- requested and given back when it is rejected.
+CREATE TABLE withdrawal_requests (
+    id bigserial PRIMARY KEY,
+    discord_id bigint NOT NULL,
+    destination text NOT NULL,
+    amount bigint NOT NULL,
+    fee bigint NOT NULL,
+    currency_id text NOT NULL,
+    status text NOT NULL DEFAULT 'pending',
+    decided_by bigint,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE INDEX withdrawal_requests_pending_idx ON withdrawal_requests (created_at) WHERE status = 'pending';
+
+CREATE TRIGGER set_updated_timestamp BEFORE UPDATE ON withdrawal_requests FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
@@ -0,0 +1,11 @@
+-- adjustments are part of the balance history, so don't throw them away silently.
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM transactions WHERE transaction_action = 'adjustment') THEN
+        RAISE EXCEPTION 'transactions contains balance adjustments, move them elsewhere before reverting';
+    END IF;
+END
+$$;
+
+ALTER TABLE transactions DROP COLUMN issued_by;
+ALTER TABLE transactions DROP COLUMN reason;
@@ -0,0 +1,4 @@
+-- balance adjustments by admins are stored as transactions with transaction_action = 'adjustment'.
+-- They have no txid or address, the amount is negative for debits.
+ALTER TABLE transactions ADD COLUMN issued_by bigint;
+ALTER TABLE transactions ADD COLUMN reason text;
@@ -0,0 +1 @@
+ALTER TABLE tips ALTER COLUMN counterparty TYPE text USING counterparty::text;
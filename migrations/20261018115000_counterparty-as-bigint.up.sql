@@ -0,0 +1,3 @@
+-- counterparty always holds the Discord id of the tipper, like discord_id holds the id of the
+-- recipient, so it gets the same type.
+ALTER TABLE tips ALTER COLUMN counterparty TYPE bigint USING counterparty::bigint;
@@ -0,0 +1,6 @@
+DROP INDEX tips_counterparty_idx;
+DROP INDEX tips_discord_id_idx;
+DROP INDEX tips_guild_id_created_at_idx;
+
+ALTER TABLE reactdrops DROP COLUMN guild_id;
+ALTER TABLE tips DROP COLUMN guild_id;
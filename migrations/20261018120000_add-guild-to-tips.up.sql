@@ -0,0 +1,7 @@
+-- tips and reactdrops from before this migration have no guild
+ALTER TABLE tips ADD COLUMN guild_id bigint;
+ALTER TABLE reactdrops ADD COLUMN guild_id bigint;
+
+CREATE INDEX tips_guild_id_created_at_idx ON tips (guild_id, created_at);
+CREATE INDEX tips_discord_id_idx ON tips (discord_id);
+CREATE INDEX tips_counterparty_idx ON tips (counterparty);
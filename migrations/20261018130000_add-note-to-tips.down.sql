@@ -0,0 +1 @@
+ALTER TABLE tips DROP COLUMN note;
@@ -0,0 +1,15 @@
+CREATE TABLE outbox (
+    id bigserial PRIMARY KEY,
+    discord_id bigint,
+    channel_id bigint,
+    payload text NOT NULL,
+    status text DEFAULT 'pending' NOT NULL,
+    attempts integer DEFAULT 0 NOT NULL,
+    last_error text,
+    next_attempt_at timestamp with time zone DEFAULT now() NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    delivered_at timestamp with time zone,
+    CONSTRAINT outbox_one_recipient CHECK ((discord_id IS NULL) <> (channel_id IS NULL))
+);
+
+CREATE INDEX outbox_pending_idx ON outbox (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,7 @@
+ALTER TABLE notifications
+    DROP COLUMN deposit_confirmed,
+    DROP COLUMN withdrawal_sent,
+    DROP COLUMN direct_tip,
+    DROP COLUMN group_tip,
+    DROP COLUMN reactdrop_win,
+    DROP COLUMN price_alert;
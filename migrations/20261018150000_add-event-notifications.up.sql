@@ -0,0 +1,7 @@
+ALTER TABLE notifications
+    ADD COLUMN deposit_confirmed text,
+    ADD COLUMN withdrawal_sent text,
+    ADD COLUMN direct_tip text,
+    ADD COLUMN group_tip text,
+    ADD COLUMN reactdrop_win text,
+    ADD COLUMN price_alert text;
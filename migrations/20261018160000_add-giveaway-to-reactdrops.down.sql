@@ -0,0 +1,5 @@
+ALTER TABLE reactdrops DROP COLUMN winners;
+ALTER TABLE reactdrops DROP COLUMN draw_seed;
+ALTER TABLE reactdrops DROP COLUMN draw_height;
+ALTER TABLE reactdrops DROP COLUMN entrants;
+ALTER TABLE reactdrops DROP COLUMN excluded_entrants;
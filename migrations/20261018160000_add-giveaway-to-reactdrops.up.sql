@@ -0,0 +1,9 @@
+-- a reactdrop with winners is a giveaway: only that many of the participants get a share
+ALTER TABLE reactdrops ADD COLUMN winners integer;
+ALTER TABLE reactdrops ADD COLUMN draw_seed text;
+-- the winners of a giveaway are drawn with the hash of a block that is committed to when the
+-- giveaway starts. When the giveaway ends its entries are stored, so nobody can join after the
+-- hash is known.
+ALTER TABLE reactdrops ADD COLUMN draw_height bigint;
+ALTER TABLE reactdrops ADD COLUMN entrants bigint[];
+ALTER TABLE reactdrops ADD COLUMN excluded_entrants integer;
@@ -0,0 +1 @@
+ALTER TABLE reactdrops DROP COLUMN escrowed;
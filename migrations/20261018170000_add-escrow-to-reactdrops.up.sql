@@ -0,0 +1,3 @@
+-- the amount of an escrowed reactdrop is taken from the author when the reactdrop starts.
+-- Reactdrops from before this migration were not escrowed and are paid by the author when they end.
+ALTER TABLE reactdrops ADD COLUMN escrowed boolean NOT NULL DEFAULT false;
@@ -0,0 +1,2 @@
+-- a short id that users can refer to when managing their reactdrops
+ALTER TABLE reactdrops ADD COLUMN id bigserial NOT NULL UNIQUE;
@@ -0,0 +1,4 @@
+ALTER TABLE reactdrops DROP COLUMN required_role_id;
+ALTER TABLE reactdrops DROP COLUMN min_account_age_days;
+ALTER TABLE reactdrops DROP COLUMN min_membership_days;
+ALTER TABLE reactdrops DROP COLUMN max_participants;
@@ -0,0 +1,5 @@
+-- optional constraints on who can participate in a reactdrop
+ALTER TABLE reactdrops ADD COLUMN required_role_id bigint;
+ALTER TABLE reactdrops ADD COLUMN min_account_age_days integer;
+ALTER TABLE reactdrops ADD COLUMN min_membership_days integer;
+ALTER TABLE reactdrops ADD COLUMN max_participants integer;
@@ -0,0 +1,3 @@
+ALTER TABLE reactdrops DROP COLUMN attempts;
+ALTER TABLE reactdrops DROP COLUMN last_error;
+ALTER TABLE reactdrops DROP COLUMN next_attempt_at;
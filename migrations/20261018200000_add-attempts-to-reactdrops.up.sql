@@ -0,0 +1,5 @@
+-- failed attempts to end a reactdrop; after too many the reactdrop is given up on. Until then
+-- it is tried again after a delay that grows with every attempt.
+ALTER TABLE reactdrops ADD COLUMN attempts integer NOT NULL DEFAULT 0;
+ALTER TABLE reactdrops ADD COLUMN last_error text;
+ALTER TABLE reactdrops ADD COLUMN next_attempt_at timestamptz;
@@ -0,0 +1,23 @@
+-- tips and reactdrops that start at a later time. The amount is taken from the author when the
+-- tip is scheduled, and given back if it can't be carried out. A scheduled tip is claimed
+-- ('running') before anything is posted, so it is never carried out twice. Failed attempts are
+-- retried later, until there were too many.
+CREATE TABLE scheduled_tips (
+    id bigserial PRIMARY KEY,
+    author bigint NOT NULL,
+    guild_id bigint NOT NULL,
+    channel_id bigint NOT NULL,
+    amount bigint NOT NULL,
+    currency_id text NOT NULL,
+    action text NOT NULL,
+    run_at timestamp with time zone NOT NULL,
+    status text NOT NULL DEFAULT 'pending',
+    attempts integer NOT NULL DEFAULT 0,
+    last_error text,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE INDEX scheduled_tips_pending_idx ON scheduled_tips (run_at) WHERE status = 'pending';
+
+CREATE TRIGGER set_updated_timestamp BEFORE UPDATE ON scheduled_tips FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
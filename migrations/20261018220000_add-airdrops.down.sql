@@ -0,0 +1,2 @@
+DROP TABLE airdrop_runs;
+DROP TABLE airdrops;
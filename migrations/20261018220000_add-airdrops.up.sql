@@ -0,0 +1,31 @@
+-- recurring airdrops, configured by admins. Every run takes the amount from the balance of the
+-- source account and tips a role or starts a reactdrop.
+CREATE TABLE airdrops (
+    id bigserial PRIMARY KEY,
+    guild_id bigint NOT NULL,
+    channel_id bigint NOT NULL,
+    source_account bigint NOT NULL,
+    amount bigint NOT NULL,
+    currency_id text NOT NULL,
+    action text NOT NULL,
+    schedule text NOT NULL,
+    next_run_at timestamp with time zone NOT NULL,
+    enabled boolean NOT NULL DEFAULT TRUE,
+    created_by bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE INDEX airdrops_enabled_idx ON airdrops (next_run_at) WHERE enabled;
+
+CREATE TRIGGER set_updated_timestamp BEFORE UPDATE ON airdrops FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
+
+CREATE TABLE airdrop_runs (
+    id bigserial PRIMARY KEY,
+    airdrop_id bigint NOT NULL REFERENCES airdrops (id),
+    status text NOT NULL,
+    error text,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE INDEX airdrop_runs_airdrop_id_idx ON airdrop_runs (airdrop_id, created_at);
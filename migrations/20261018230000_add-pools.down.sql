@@ -0,0 +1,3 @@
+ALTER TABLE reactdrops DROP COLUMN spender;
+ALTER TABLE scheduled_tips DROP COLUMN spender;
+DROP TABLE pools;
@@ -0,0 +1,20 @@
+-- shared accounts like the treasury of a server. The balance, addresses and history of a pool are
+-- stored under the negative of its id, so they don't clash with Discord user ids.
+CREATE TABLE pools (
+    id bigserial PRIMARY KEY,
+    guild_id bigint NOT NULL,
+    name text NOT NULL,
+    spender_role_id bigint,
+    created_by bigint NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+CREATE UNIQUE INDEX pools_guild_id_name_idx ON pools (guild_id, lower(name));
+
+CREATE TRIGGER set_updated_timestamp BEFORE UPDATE ON pools FOR EACH ROW EXECUTE FUNCTION trigger_set_timestamp();
+
+-- the member that spent the amount of a reactdrop or a scheduled tip, which differs from the
+-- author when it is paid from a pool. The spender gets no share of it.
+ALTER TABLE reactdrops ADD COLUMN spender bigint;
+ALTER TABLE scheduled_tips ADD COLUMN spender bigint;
@@ -0,0 +1,75 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use poise::serenity_prelude::{ChannelId, GuildId, UserId};
+
+/// How long message activity is remembered. Commands can't look back further than this.
+pub const MAX_ACTIVITY_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Keeps track of who recently posted a message in which channel and guild, so tips can be sent to the
+/// users that are actually active. Only kept in memory: after a restart the bot needs to see
+/// new messages before anyone counts as active again.
+#[derive(Debug, Default)]
+pub struct RecentActivity {
+    channels: Mutex<HashMap<ChannelId, HashMap<UserId, Instant>>>,
+    guilds: Mutex<HashMap<GuildId, HashMap<UserId, Instant>>>,
+}
+
+impl RecentActivity {
+    pub fn record(&self, guild_id: Option<GuildId>, channel_id: ChannelId, user_id: UserId) {
+        let now = Instant::now();
+
+        let mut channels = self.channels.lock().unwrap();
+        channels.entry(channel_id).or_default().insert(user_id, now);
+        forget_inactive(&mut channels, now);
+
+        if let Some(guild_id) = guild_id {
+            let mut guilds = self.guilds.lock().unwrap();
+            guilds.entry(guild_id).or_default().insert(user_id, now);
+            forget_inactive(&mut guilds, now);
+        }
+    }
+
+    /// Returns the users that posted a message in `channel_id` during the last `window`.
+    pub fn active_in_channel(&self, channel_id: ChannelId, window: Duration) -> Vec<UserId> {
+        let channels = self.channels.lock().unwrap();
+
+        channels
+            .get(&channel_id)
+            .map(|users| {
+                users
+                    .iter()
+                    .filter(|(_, last_seen)| last_seen.elapsed() <= window)
+                    .map(|(user_id, _)| *user_id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the users that posted a message anywhere in `guild_id` during the last `window`.
+    pub fn active_in_guild(&self, guild_id: GuildId, window: Duration) -> Vec<UserId> {
+        let guilds = self.guilds.lock().unwrap();
+
+        guilds
+            .get(&guild_id)
+            .map(|users| {
+                users
+                    .iter()
+                    .filter(|(_, last_seen)| last_seen.elapsed() <= window)
+                    .map(|(user_id, _)| *user_id)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+// forget everything that is too old to ever be asked for, to keep the maps small.
+fn forget_inactive<K>(map: &mut HashMap<K, HashMap<UserId, Instant>>, now: Instant) {
+    map.retain(|_, users| {
+        users.retain(|_, last_seen| now.duration_since(*last_seen) <= MAX_ACTIVITY_WINDOW);
+        !users.is_empty()
+    });
+}
@@ -0,0 +1,298 @@
+use std::{fmt::Display, str::FromStr, sync::Arc, time::Duration};
+
+use poise::serenity_prelude::{
+    ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Http, UserId,
+};
+use sqlx::{
+    PgConnection, PgPool,
+    types::chrono::{DateTime, Utc},
+};
+use tokio::{sync::Notify, time::sleep};
+use tokio_graceful_shutdown::{IntoSubsystem, SubsystemHandle};
+use tracing::{debug, error, info, warn};
+use vrsc::{Address, Amount};
+
+use crate::{
+    Error,
+    config::Config,
+    database,
+    notifier::{self, Recipient},
+    pool,
+    scheduler::{self, ScheduledAction},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A recurring airdrop, configured by an admin. Every run takes the amount from the balance of
+/// the source account and carries out the action.
+#[derive(Debug)]
+pub struct Airdrop {
+    pub id: i64,
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub source: UserId,
+    pub amount: Amount,
+    pub currency_id: Address,
+    pub action: ScheduledAction,
+    /// A cron expression: `sec min hour day-of-month month day-of-week [year]`, in UTC.
+    pub schedule: String,
+    pub next_run_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum AirdropRunStatus {
+    Succeeded,
+    Failed,
+}
+
+impl Display for AirdropRunStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Succeeded => write!(f, "succeeded"),
+            Self::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AirdropRun {
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Display for AirdropRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<t:{}:f> {}", self.created_at.timestamp(), self.status)?;
+
+        if let Some(error) = &self.error {
+            write!(f, ": {error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The first time after `after` the cron expression `schedule` fires, or `None` when it never
+/// fires again.
+pub fn next_run(schedule: &str, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, Error> {
+    let schedule = cron::Schedule::from_str(schedule)?;
+
+    Ok(schedule.after(&after).next())
+}
+
+/// Runs the recurring airdrops when they are due.
+pub struct Subsystem {
+    pub http: Arc<Http>,
+    pub pool: PgPool,
+    pub config: Config,
+    /// Wakes up the reactdrop subsystem when an airdrop started a reactdrop.
+    pub reactdrop_wakeup: Arc<Notify>,
+}
+
+impl Subsystem {
+    async fn run_due_airdrops(&self) -> Result<(), Error> {
+        let mut conn = self.pool.acquire().await?;
+        let due = database::get_airdrops(&mut conn, true).await?;
+
+        for airdrop in due {
+            debug!(
+                "running airdrop {}, due at {}",
+                airdrop.id, airdrop.next_run_at
+            );
+
+            if let Err(e) = self.run(&airdrop).await {
+                error!("airdrop {} failed: {e:?}", airdrop.id);
+
+                if let Err(e) = self.fail(&airdrop, e).await {
+                    error!(
+                        "could not record the failure of airdrop {}: {e:?}",
+                        airdrop.id
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run(&self, airdrop: &Airdrop) -> Result<(), Error> {
+        // airdrops added before they had to be paid from a pool could drain a user's balance
+        if pool::pool_id(airdrop.source).is_none() {
+            return Err(format!("the source account <@{}> is not a pool", airdrop.source).into());
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let balance = Amount::from_sat(
+            database::get_balance_for_user(&mut tx, airdrop.source, &airdrop.currency_id)
+                .await?
+                .unwrap_or(0),
+        );
+        if balance < airdrop.amount {
+            return Err(format!(
+                "the balance of the source account {} is {balance}, {} is needed",
+                pool::mention(airdrop.source),
+                airdrop.amount
+            )
+            .into());
+        }
+
+        database::decrease_balance(
+            &mut tx,
+            &airdrop.source,
+            &airdrop.amount,
+            &Amount::ZERO,
+            &airdrop.currency_id,
+        )
+        .await?;
+
+        let announcement = scheduler::carry_out(
+            &self.http,
+            &self.config.application.verus_client()?,
+            &mut tx,
+            airdrop.source,
+            None,
+            airdrop.guild_id,
+            airdrop.channel_id,
+            airdrop.amount,
+            &airdrop.action,
+        )
+        .await?;
+
+        let finished = async {
+            if !self
+                .finish(&mut tx, airdrop, AirdropRunStatus::Succeeded, None)
+                .await?
+            {
+                // dropping the transaction rolls everything back
+                return Err(format!("airdrop {} already ran", airdrop.id).into());
+            }
+
+            tx.commit().await.map_err(Error::from)
+        };
+
+        if let Err(e) = finished.await {
+            scheduler::discard(&self.http, announcement).await;
+
+            return Err(e);
+        }
+
+        info!("ran airdrop: {airdrop:#?}");
+
+        if matches!(airdrop.action, ScheduledAction::Reactdrop { .. }) {
+            self.reactdrop_wakeup.notify_one();
+        }
+
+        Ok(())
+    }
+
+    /// Records a run that could not be carried out and reports it in the admin thread. Nothing
+    /// was taken from the source account.
+    async fn fail(&self, airdrop: &Airdrop, error: Error) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let error = error.to_string();
+        if !self
+            .finish(&mut tx, airdrop, AirdropRunStatus::Failed, Some(&error))
+            .await?
+        {
+            return Ok(());
+        }
+
+        let admin_thread = ChannelId::new(
+            self.config
+                .application
+                .discord_admin_thread_id
+                .parse::<u64>()?,
+        );
+        notifier::queue(
+            &mut tx,
+            Recipient::Channel(admin_thread),
+            &CreateMessage::new()
+                .content(format!(
+                    ":warning: airdrop #{} of {} from {} failed: {error}",
+                    airdrop.id,
+                    airdrop.amount,
+                    pool::mention(airdrop.source)
+                ))
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Stores the run in the history of the airdrop and moves it to its next run. Returns false
+    /// when the run was already recorded.
+    async fn finish(
+        &self,
+        conn: &mut PgConnection,
+        airdrop: &Airdrop,
+        status: AirdropRunStatus,
+        error: Option<&str>,
+    ) -> Result<bool, Error> {
+        let next_run_at = match next_run(&airdrop.schedule, Utc::now()) {
+            Ok(next_run_at) => next_run_at,
+            Err(e) => {
+                warn!("airdrop {} has an invalid schedule: {e:?}", airdrop.id);
+
+                None
+            }
+        };
+
+        if !database::advance_airdrop(&mut *conn, airdrop.id, airdrop.next_run_at, next_run_at)
+            .await?
+        {
+            return Ok(false);
+        }
+
+        database::insert_airdrop_run(conn, airdrop.id, status, error).await?;
+
+        Ok(true)
+    }
+}
+
+impl IntoSubsystem<Error> for Subsystem {
+    async fn run(self, subsys: &mut SubsystemHandle) -> Result<(), Error> {
+        while !subsys.is_shutdown_requested() {
+            if let Err(e) = self.run_due_airdrops().await {
+                error!(?e, "Something went wrong when running airdrops");
+            }
+
+            tokio::select! {
+                _ = sleep(POLL_INTERVAL) => {}
+                _ = subsys.on_shutdown_requested() => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::types::chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn next_runs() {
+        // a thursday
+        let after = Utc.with_ymd_and_hms(2026, 10, 15, 10, 0, 0).unwrap();
+
+        assert_eq!(
+            next_run("0 0 12 * * Fri", after).unwrap(),
+            Some(Utc.with_ymd_and_hms(2026, 10, 16, 12, 0, 0).unwrap())
+        );
+        assert_eq!(
+            next_run("0 30 * * * *", after).unwrap(),
+            Some(Utc.with_ymd_and_hms(2026, 10, 15, 10, 30, 0).unwrap())
+        );
+        // only in a year that has passed
+        assert_eq!(next_run("0 0 12 1 1 * 2020", after).unwrap(), None);
+        assert!(next_run("every friday", after).is_err());
+    }
+}
@@ -1,31 +1,132 @@
 use poise::{
     CreateReply,
-    serenity_prelude::{CreateEmbed, UserId},
+    serenity_prelude::{
+        ButtonStyle, ChannelId, ComponentInteraction, ComponentInteractionCollector,
+        ComponentInteractionDataKind, CreateActionRow, CreateAllowedMentions, CreateButton,
+        CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+        CreateSelectMenu, CreateSelectMenuKind, GuildId, ReactionType, RoleId, UserId,
+    },
 };
-use sqlx::{Postgres, Transaction};
-use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+use sqlx::{
+    PgConnection, Postgres, Transaction,
+    types::chrono::{DateTime, Utc},
+};
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Arc, time::Duration};
 use tracing::{debug, instrument, trace};
+use uuid::Uuid;
 use vrsc::{Address, Amount};
 use vrsc_rpc::{bitcoin::Txid, client::RpcApi};
 
 use crate::{
-    Context, Error, VRSC_CURRENCY_ID, database,
+    Context, Data, Error, VRSC_CURRENCY_ID, airdrop,
+    commands::{
+        history,
+        reactdrop::Hms,
+        wallet::{self, WithdrawalRequestStatus},
+    },
+    config::AdminRole,
+    database,
+    notifier::{self, Recipient},
+    pool,
+    reactdrop::Requirements,
+    scheduler::ScheduledAction,
     wallet_listener::{TransactionProcessor, process_txid},
 };
 
+/// Returns the highest admin role of a user, if any.
+///
+/// Roles come from three places: the owners in the config, roles granted with `!grantrole`
+/// and Discord roles in the bot's guild that are mapped in the config.
+pub async fn admin_role(ctx: Context<'_>, user_id: UserId) -> Result<Option<AdminRole>, Error> {
+    if ctx.data().owners.contains(&user_id) {
+        return Ok(Some(AdminRole::Superadmin));
+    }
+
+    let mut conn = ctx.data().database.acquire().await?;
+    let granted = database::get_admin_role(&mut conn, user_id).await?;
+
+    let admin_roles = &ctx.data().settings.application.admin_roles;
+    let from_discord = ctx
+        .data()
+        .settings
+        .application
+        .discord_guild_id
+        .parse::<u64>()
+        .ok()
+        .and_then(|guild_id| {
+            ctx.cache().guild(GuildId::new(guild_id)).and_then(|guild| {
+                guild
+                    .members
+                    .get(&user_id)
+                    .map(|member| member.roles.clone())
+            })
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|role_id: RoleId| admin_roles.get(&role_id.to_string()).copied())
+        .max();
+
+    Ok(granted.max(from_discord))
+}
+
+async fn has_admin_role(ctx: Context<'_>, required: AdminRole) -> Result<bool, Error> {
+    let role = admin_role(ctx, ctx.author().id).await?;
+    trace!(?role, ?required, "checking admin role");
+
+    Ok(role.is_some_and(|role| role >= required))
+}
+
+pub async fn is_support(ctx: Context<'_>) -> Result<bool, Error> {
+    has_admin_role(ctx, AdminRole::Support).await
+}
+
+pub async fn is_treasurer(ctx: Context<'_>) -> Result<bool, Error> {
+    has_admin_role(ctx, AdminRole::Treasurer).await
+}
+
+pub async fn is_superadmin(ctx: Context<'_>) -> Result<bool, Error> {
+    has_admin_role(ctx, AdminRole::Superadmin).await
+}
+
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
 pub async fn adminhelp(ctx: Context<'_>) -> Result<(), Error> {
     ctx.send(CreateReply::default().ephemeral(true).content(
         r#"```
+support:
 !status                         - (financial) status of the bot
+!checktxid <txid>               - manually check txid (in case user balance was not updated)
+!auditlog                       - show the log of admin actions, newest first
+!banned_balances                - (in a server) balances of banned users
+!withdrawals                    - list the withdrawals that wait for approval
+!airdrops                       - list the recurring airdrops
+!airdropruns <id>               - show the most recent runs of an airdrop
+
+treasurer:
+!setwithdrawfee <sats>          - sets the fee a user is charged when withdrawing funds
+!approvewithdrawal <id>         - send a withdrawal that waits for approval
+!rejectwithdrawal <id> <reason> - reject a withdrawal that waits for approval
+!credit <user_id> <amount> <currency> <reason>
+                                - add funds to a user's balance, the reason is mandatory
+!debit <user_id> <amount> <currency> <reason>
+                                - remove funds from a user's balance, the reason is mandatory
+!airdroprole <guild_id> <channel_id> <pool> <role_id> <amount> <currency> <schedule>
+                                - tip a role from the balance of a pool on a schedule
+!airdropreactdrop <guild_id> <channel_id> <pool> <emoji> <minutes> <amount> <currency> <schedule>
+                                - start a reactdrop from the balance of a pool on a schedule
+                                  (schedule: cron in UTC, `sec min hour day month weekday`)
+!removeairdrop <id>             - stop a recurring airdrop
+
+superadmin:
 !blacklist <user_id>            - blacklists a user (no more tipping, deposits & withdraws)
 !rescanfromheight <blockheight> - rescan blockchain from given height
-!checktxid <txid>               - manually check txid (in case user balance was not updated)
 !withdrawenabled <true/false>   - enable / disable withdraws
 !depositenabled <true/false>    - enable / disable deposits
-!setwithdrawfee <sats>          - sets the fee a user is charged when withdrawing funds
 !maintenance <true/false>       - set maintenance mode (commands are not executed) 
+!grantrole <user_id> <role>     - make a user support, treasurer or superadmin
+!revokerole <user_id>           - remove the admin role of a user
+
+In a server, /admin opens a panel with buttons for the most common actions.
 ```"#,
     ))
     .await?;
@@ -34,7 +135,7 @@ pub async fn adminhelp(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
 pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer().await?;
 
@@ -52,8 +153,16 @@ pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
     let largest_tip = Amount::from_sat(
         database::get_largest_tip(&mut conn, &Address::from_str(VRSC_CURRENCY_ID)?).await?,
     );
+    let total_escrowed =
+        database::get_escrowed_amount(&mut conn, None, &Address::from_str(VRSC_CURRENCY_ID)?)
+            .await?;
     let total_deposited = database::get_summed_deposits(&mut conn).await?;
     let total_withdrawn = database::get_summed_withdrawals(&mut conn).await?;
+    let total_adjusted =
+        database::get_summed_adjustments(&mut conn, &Address::from_str(VRSC_CURRENCY_ID)?).await?;
+    // credits and debits by admins change balances without moving funds on chain.
+    let reconciled =
+        total_deposited.as_sat() as i64 - total_withdrawn.as_sat() as i64 + total_adjusted;
 
     let client = ctx.data().verus()?;
 
@@ -64,6 +173,7 @@ pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
     debug!("largest_tip: {largest_tip}");
     debug!("total_deposited: {total_deposited}");
     debug!("total_withdrawn: {total_withdrawn}");
+    debug!("total_adjusted: {total_adjusted} sats");
     debug!("daemon_balance: {daemon_balance}");
 
     ctx.send(
@@ -79,13 +189,25 @@ pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
                 )
                 .field("VRSC daemon balance", daemon_balance.to_string(), false)
                 .field("Tipbot balance", total_balance.to_string(), false)
+                .field(
+                    "Held in reactdrops and scheduled tips",
+                    total_escrowed.to_string(),
+                    false,
+                )
                 .field("Total deposited", total_deposited.to_string(), false)
                 .field("Total withdrawn", total_withdrawn.to_string(), false)
                 .field(
-                    "Database deposits - withdraws",
-                    total_deposited
-                        .checked_sub(total_withdrawn)
-                        .unwrap_or(Amount::ZERO)
+                    "Total adjusted by admins",
+                    format!(
+                        "{}{}",
+                        if total_adjusted < 0 { "-" } else { "" },
+                        Amount::from_sat(total_adjusted.unsigned_abs())
+                    ),
+                    false,
+                )
+                .field(
+                    "Database deposits - withdraws + adjustments",
+                    Amount::from_sat(reconciled.max(0) as u64)
                         .to_string_in(vrsc::Denomination::Verus),
                     false,
                 )
@@ -111,7 +233,7 @@ pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
 }
 
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
 pub async fn blacklist(ctx: Context<'_>, user_id: UserId) -> Result<(), Error> {
     let mut tx = ctx.data().database.begin().await?;
 
@@ -127,6 +249,16 @@ pub async fn blacklist(ctx: Context<'_>, user_id: UserId) -> Result<(), Error> {
             .await?;
             trace!("{user_id} has been removed from blacklist");
 
+            audit(
+                &mut tx,
+                ctx,
+                "blacklist",
+                user_id.to_string(),
+                Some(true.to_string()),
+                Some(false.to_string()),
+            )
+            .await?;
+
             tx.commit().await?;
 
             return Ok(());
@@ -134,6 +266,17 @@ pub async fn blacklist(ctx: Context<'_>, user_id: UserId) -> Result<(), Error> {
     }
 
     database::set_blacklist_status(&mut tx, user_id, true).await?;
+
+    audit(
+        &mut tx,
+        ctx,
+        "blacklist",
+        user_id.to_string(),
+        Some(false.to_string()),
+        Some(true.to_string()),
+    )
+    .await?;
+
     tx.commit().await?;
 
     if let Ok(mut blacklist) = ctx.data().blacklist.lock() {
@@ -149,28 +292,218 @@ pub async fn blacklist(ctx: Context<'_>, user_id: UserId) -> Result<(), Error> {
 }
 
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
 pub async fn setwithdrawfee(ctx: Context<'_>, amount: u64) -> Result<(), Error> {
-    let withdrawal_fee = &ctx.data().withdrawal_fee;
+    let old_fee = set_withdrawal_fee(ctx.data(), Amount::from_sat(amount)).await;
 
-    debug!("fee before changing: {:?}", withdrawal_fee);
+    ctx.send(CreateReply::default().content(format!("Withdraw fee set to {amount} sats")))
+        .await?;
 
-    let mut write = withdrawal_fee.write().await;
-    *write = Amount::from_sat(amount);
+    let mut conn = ctx.data().database.acquire().await?;
+    audit(
+        &mut conn,
+        ctx,
+        "setwithdrawfee",
+        amount.to_string(),
+        Some(old_fee.as_sat().to_string()),
+        Some(amount.to_string()),
+    )
+    .await?;
 
-    debug!("fee after changing: {:?}", withdrawal_fee);
-    ctx.send(CreateReply::default().content(format!("Withdraw fee set to {amount} sats")))
+    Ok(())
+}
+
+/// List the withdrawals that are waiting for approval
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
+pub async fn withdrawals(ctx: Context<'_>) -> Result<(), Error> {
+    let requests = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_pending_withdrawal_requests(&mut conn).await?
+    };
+
+    if requests.is_empty() {
+        ctx.send(CreateReply::default().content("No withdrawals are waiting for approval"))
+            .await?;
+
+        return Ok(());
+    }
+
+    let lines = requests
+        .iter()
+        .map(|request| {
+            format!(
+                "`#{}` {} (+ {} fee) from <@{}> to {}, <t:{}:R>",
+                request.id,
+                request.amount,
+                request.fee,
+                request.user_id,
+                request.destination,
+                request.created_at.timestamp()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Withdrawals waiting for approval")
+                .description(lines),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Send a withdrawal that is waiting for approval
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn approvewithdrawal(ctx: Context<'_>, id: i64) -> Result<(), Error> {
+    let client = &ctx.data().verus()?;
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(request) = database::decide_withdrawal_request(
+        &mut tx,
+        id,
+        WithdrawalRequestStatus::Sent,
+        ctx.author().id,
+    )
+    .await?
+    else {
+        ctx.send(CreateReply::default().content(format!(
+            "There is no withdrawal request #{id} waiting for approval"
+        )))
+        .await?;
+
+        return Ok(());
+    };
+
+    audit(
+        &mut tx,
+        ctx,
+        "approvewithdrawal",
+        id.to_string(),
+        Some(WithdrawalRequestStatus::Pending.to_string()),
+        Some(WithdrawalRequestStatus::Sent.to_string()),
+    )
+    .await?;
+
+    // the amount and the fee were taken from the balance when the withdrawal was requested, so
+    // the withdrawal is sent from what is held for it.
+    let uuid = Uuid::new_v4();
+    let txid = wallet::send_withdrawal(
+        &mut tx,
+        client,
+        &uuid,
+        request.user_id,
+        request.amount,
+        request.fee,
+        &request.destination,
+        &request.currency_id,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    if let Some(txid) = txid {
+        wallet::queue_withdrawal_dm(ctx, request.user_id, request.amount, &txid).await;
+
+        ctx.send(CreateReply::default().content(format!(
+            "Withdrawal #{id} was sent: https://insight.verus.io/tx/{txid}"
+        )))
         .await?;
+    } else {
+        ctx.send(CreateReply::default().content(format!(
+            "The sendcurrency of withdrawal #{id} didn't finish, check withdrawal ID {uuid} \
+            manually"
+        )))
+        .await?;
+    }
 
     Ok(())
 }
 
+/// Reject a withdrawal that is waiting for approval
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn rejectwithdrawal(
+    ctx: Context<'_>,
+    id: i64,
+    #[rest] reason: String,
+) -> Result<(), Error> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        ctx.send(CreateReply::default().content("A reason is required"))
+            .await?;
+
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(request) = database::decide_withdrawal_request(
+        &mut tx,
+        id,
+        WithdrawalRequestStatus::Rejected,
+        ctx.author().id,
+    )
+    .await?
+    else {
+        ctx.send(CreateReply::default().content(format!(
+            "There is no withdrawal request #{id} waiting for approval"
+        )))
+        .await?;
+
+        return Ok(());
+    };
+
+    // give back what was held for the withdrawal
+    database::increase_balance(
+        &mut tx,
+        &request.user_id,
+        request.amount + request.fee,
+        &request.currency_id,
+    )
+    .await?;
+
+    notifier::queue(
+        &mut tx,
+        Recipient::User(request.user_id),
+        &CreateMessage::new().content(format!(
+            "Your request #{id} to withdraw {} was rejected: {reason}\n\
+            The amount and the fee were returned to your balance.",
+            request.amount
+        )),
+    )
+    .await?;
+
+    audit(
+        &mut tx,
+        ctx,
+        "rejectwithdrawal",
+        format!("{id} {reason}"),
+        Some(WithdrawalRequestStatus::Pending.to_string()),
+        Some(WithdrawalRequestStatus::Rejected.to_string()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    ctx.send(CreateReply::default().content(format!("Withdrawal request #{id} was rejected")))
+        .await?;
+
+    Ok(())
+}
+
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
 pub async fn rescanfromheight(ctx: Context<'_>, height: u64) -> Result<(), Error> {
     trace!("Initiating a rescan from height {height}");
 
     let client = &ctx.data().verus()?;
+    let mut conn = ctx.data().database.acquire().await?;
     if let Ok(()) = client.rescan_from_height(height) {
         trace!("rescan done");
 
@@ -179,100 +512,777 @@ pub async fn rescanfromheight(ctx: Context<'_>, height: u64) -> Result<(), Error
         ctx.data().tx_processor.process_short_queue().await?;
         ctx.send(CreateReply::default().content("Rescan done"))
             .await?;
+
+        audit(
+            &mut conn,
+            ctx,
+            "rescanfromheight",
+            height.to_string(),
+            None,
+            Some("done".to_string()),
+        )
+        .await?;
     } else {
-        trace!("rescan did not succeed")
+        trace!("rescan did not succeed");
+
+        audit(
+            &mut conn,
+            ctx,
+            "rescanfromheight",
+            height.to_string(),
+            None,
+            Some("failed".to_string()),
+        )
+        .await?;
     }
 
     Ok(())
 }
 
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
 pub async fn withdrawenabled(ctx: Context<'_>, value: bool) -> Result<(), Error> {
     trace!("set withdraws enabled to {value}");
 
-    {
-        let withdrawals_enabled = &ctx.data().withdrawals_enabled;
-        let mut write = withdrawals_enabled.write().await;
-        *write = value;
-    }
+    let old_value = set_withdrawals_enabled(ctx.data(), value).await;
 
     ctx.send(CreateReply::default().content(format!("Withdraws enabled: {value}")))
         .await?;
 
+    let mut conn = ctx.data().database.acquire().await?;
+    audit(
+        &mut conn,
+        ctx,
+        "withdrawenabled",
+        value.to_string(),
+        Some(old_value.to_string()),
+        Some(value.to_string()),
+    )
+    .await?;
+
     Ok(())
 }
 
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
 pub async fn depositenabled(ctx: Context<'_>, value: bool) -> Result<(), Error> {
     trace!("set deposits enabled to {value}");
 
-    {
-        let mut tx = ctx.data().database.begin().await?;
-        let deposits_enabled = &ctx.data().deposits_enabled;
-        let mut write = deposits_enabled.write().await;
-        if *write && !value {
-            trace!("need to process possible unprocessed transactions");
-
-            let tx_proc = Arc::clone(&ctx.data().tx_processor);
-
-            process_stored_txids(&mut tx, tx_proc).await?
-        }
-        tx.commit().await?;
-        *write = value;
-    }
+    let old_value = set_deposits_enabled(ctx.data(), value).await?;
 
     ctx.send(CreateReply::default().content(format!("Deposits enabled: {value}")))
         .await?;
 
+    let mut conn = ctx.data().database.acquire().await?;
+    audit(
+        &mut conn,
+        ctx,
+        "depositenabled",
+        value.to_string(),
+        Some(old_value.to_string()),
+        Some(value.to_string()),
+    )
+    .await?;
+
     Ok(())
 }
 
 /// Manually checks a tx if it was not caught with rescan
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
 pub async fn checktxid(ctx: Context<'_>, txid: Txid) -> Result<(), Error> {
     trace!("manually check {txid}");
-    let http = ctx.serenity_context().http.clone();
     let mut tx = ctx.data().database.begin().await?;
 
     let client = &ctx.data().verus()?;
 
-    if let Ok(raw_tx) = client.get_raw_transaction_verbose(&txid) {
-        process_txid(http, &mut tx, &raw_tx).await?;
-        tx.commit().await?;
-    }
+    let result = if let Ok(raw_tx) = client.get_raw_transaction_verbose(&txid) {
+        process_txid(&mut tx, &raw_tx).await?;
+
+        "processed"
+    } else {
+        "not found"
+    };
+
+    audit(
+        &mut tx,
+        ctx,
+        "checktxid",
+        txid.to_string(),
+        None,
+        Some(result.to_string()),
+    )
+    .await?;
+
+    tx.commit().await?;
 
     Ok(())
 }
 
 /// Set maintenance mode on or off
 #[instrument(skip(ctx))]
-#[poise::command(dm_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
 pub async fn maintenance(ctx: Context<'_>, value: bool) -> Result<(), Error> {
     trace!("setting maintenance mode to {value}");
 
-    {
-        let mut tx = ctx.data().database.begin().await?;
-        let mut write = ctx.data().tx_processor.maintenance.write().await;
-        if *write && !value {
-            trace!("need to process possible unprocessed transactions");
+    let old_value = set_maintenance(ctx.data(), value).await?;
 
-            let tx_proc = Arc::clone(&ctx.data().tx_processor);
+    ctx.send(CreateReply::default().content(format!("Maintenance mode set to {value}")))
+        .await?;
 
-            process_stored_txids(&mut tx, tx_proc).await?
+    let mut conn = ctx.data().database.acquire().await?;
+    audit(
+        &mut conn,
+        ctx,
+        "maintenance",
+        value.to_string(),
+        Some(old_value.to_string()),
+        Some(value.to_string()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Add funds to the balance of a user
+///
+/// Use this to correct a balance, e.g. after a deposit that was not credited.
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn credit(
+    ctx: Context<'_>,
+    user_id: UserId,
+    amount: f64,
+    currency: String,
+    #[rest] reason: String,
+) -> Result<(), Error> {
+    adjust_balance(ctx, user_id, amount, &currency, &reason, Adjustment::Credit).await
+}
+
+/// Remove funds from the balance of a user
+///
+/// Use this to correct a balance, e.g. after a failed withdrawal that was not reverted.
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn debit(
+    ctx: Context<'_>,
+    user_id: UserId,
+    amount: f64,
+    currency: String,
+    #[rest] reason: String,
+) -> Result<(), Error> {
+    adjust_balance(ctx, user_id, amount, &currency, &reason, Adjustment::Debit).await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Adjustment {
+    Credit,
+    Debit,
+}
+
+async fn adjust_balance(
+    ctx: Context<'_>,
+    user_id: UserId,
+    amount: f64,
+    currency: &str,
+    reason: &str,
+    adjustment: Adjustment,
+) -> Result<(), Error> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        ctx.say("A reason is required for a balance adjustment")
+            .await?;
+
+        return Ok(());
+    }
+
+    let amount = match Amount::from_vrsc(amount) {
+        Ok(amount) if amount > Amount::ZERO => amount,
+        _ => {
+            ctx.say("The amount should be more than 0").await?;
+
+            return Ok(());
+        }
+    };
+
+    let Ok(currency_id) = ctx.data().to_currency_id(currency) else {
+        ctx.say(format!("Currency `{currency}` not found")).await?;
+
+        return Ok(());
+    };
+
+    let mut tx = ctx.data().database.begin().await?;
+
+    let old_balance = Amount::from_sat(
+        database::get_balance_for_user(&mut tx, user_id, &currency_id)
+            .await?
+            .unwrap_or(0),
+    );
+
+    let signed_amount = match adjustment {
+        Adjustment::Credit => {
+            database::increase_balance(&mut tx, &user_id, amount, &currency_id).await?;
+
+            amount.as_sat() as i64
+        }
+        Adjustment::Debit => {
+            if old_balance < amount {
+                ctx.say(format!(
+                    "Can't debit {amount}, the balance of user {user_id} is {old_balance}"
+                ))
+                .await?;
+
+                return Ok(());
+            }
+
+            database::decrease_balance(&mut tx, &user_id, &amount, &Amount::ZERO, &currency_id)
+                .await?;
+
+            -(amount.as_sat() as i64)
+        }
+    };
+
+    let uuid = Uuid::new_v4();
+    database::store_adjustment_transaction(
+        &mut tx,
+        &uuid,
+        &user_id,
+        &currency_id,
+        signed_amount,
+        &ctx.author().id,
+        reason,
+    )
+    .await?;
+
+    let new_balance = Amount::from_sat(
+        database::get_balance_for_user(&mut tx, user_id, &currency_id)
+            .await?
+            .unwrap_or(0),
+    );
+
+    let currency_name = ctx
+        .data()
+        .to_currency_name(&currency_id)
+        .unwrap_or_else(|_| currency_id.to_string());
+
+    let notification = match adjustment {
+        Adjustment::Credit => {
+            format!("An admin added {amount} {currency_name} to your balance.\nReason: {reason}")
         }
-        tx.commit().await?;
-        *write = value;
+        Adjustment::Debit => format!(
+            "An admin removed {amount} {currency_name} from your balance.\nReason: {reason}"
+        ),
+    };
+
+    notifier::queue(
+        &mut tx,
+        Recipient::User(user_id),
+        &CreateMessage::new().content(notification),
+    )
+    .await?;
+
+    let action = match adjustment {
+        Adjustment::Credit => "credit",
+        Adjustment::Debit => "debit",
+    };
+
+    audit(
+        &mut tx,
+        ctx,
+        action,
+        format!("{user_id} {amount} {currency_name} {reason}"),
+        Some(old_balance.to_string()),
+        Some(new_balance.to_string()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    trace!(%uuid, ?adjustment, "balance of {user_id} adjusted from {old_balance} to {new_balance}");
+
+    ctx.say(format!(
+        "Balance of user {user_id} adjusted: {old_balance} → {new_balance} {currency_name} \
+        (adjustment ID: {uuid})"
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Add a recurring airdrop that tips the members of a role
+///
+/// The amount comes from a pool of the guild, so an airdrop can't drain the balance of a user.
+/// The schedule is a cron expression in UTC with seconds: `sec min hour day-of-month month
+/// day-of-week [year]`, e.g. `0 0 18 * * Fri` for every Friday at 18:00.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn airdroprole(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    pool: String,
+    role_id: RoleId,
+    amount: f64,
+    currency: String,
+    #[rest] schedule: String,
+) -> Result<(), Error> {
+    let action = ScheduledAction::RoleTip {
+        role_id,
+        note: None,
+        max_recipients: None,
+    };
+
+    add_airdrop(
+        ctx, guild_id, channel_id, &pool, amount, &currency, action, &schedule,
+    )
+    .await
+}
+
+/// Add a recurring airdrop that starts a reactdrop
+///
+/// Everyone who reacts within `minutes` shares the amount. The schedule is a cron expression, see
+/// `!airdroprole`.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn airdropreactdrop(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    pool: String,
+    emoji: String,
+    minutes: u32,
+    amount: f64,
+    currency: String,
+    #[rest] schedule: String,
+) -> Result<(), Error> {
+    if ReactionType::try_from(emoji.as_str()).is_err() {
+        ctx.say(format!("`{emoji}` is not an emoji")).await?;
+
+        return Ok(());
     }
 
-    ctx.send(CreateReply::default().content(format!("Maintenance mode set to {value}")))
+    let Some(duration) = Some(minutes)
+        .filter(|minutes| *minutes > 0)
+        .and_then(|minutes| Hms::Minutes.duration(minutes as i64))
+    else {
+        ctx.say("A reactdrop runs between 1 minute and 30 days")
+            .await?;
+
+        return Ok(());
+    };
+
+    let action = ScheduledAction::Reactdrop {
+        emoji,
+        duration_secs: duration.num_seconds(),
+        winners: None,
+        requirements: Requirements::default(),
+    };
+
+    add_airdrop(
+        ctx, guild_id, channel_id, &pool, amount, &currency, action, &schedule,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn add_airdrop(
+    ctx: Context<'_>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    pool: &str,
+    amount: f64,
+    currency: &str,
+    action: ScheduledAction,
+    schedule: &str,
+) -> Result<(), Error> {
+    let schedule = schedule.trim();
+
+    let mut conn = ctx.data().database.acquire().await?;
+    let Some(pool) = database::get_pool(&mut conn, guild_id, pool).await? else {
+        ctx.say(format!("There is no pool `{pool}` in guild {guild_id}"))
+            .await?;
+
+        return Ok(());
+    };
+    let source = pool.account();
+
+    let amount = match Amount::from_vrsc(amount) {
+        Ok(amount) if amount > Amount::ZERO => amount,
+        _ => {
+            ctx.say("The amount should be more than 0").await?;
+
+            return Ok(());
+        }
+    };
+
+    let Ok(currency_id) = ctx.data().to_currency_id(currency) else {
+        ctx.say(format!("Currency `{currency}` not found")).await?;
+
+        return Ok(());
+    };
+
+    // tips and reactdrops are VRSC only
+    if currency_id.to_string() != VRSC_CURRENCY_ID {
+        ctx.say("Only VRSC airdrops are supported for now").await?;
+
+        return Ok(());
+    }
+
+    let next_run_at = match airdrop::next_run(schedule, Utc::now()) {
+        Ok(Some(next_run_at)) => next_run_at,
+        Ok(None) => {
+            ctx.say(format!("The schedule `{schedule}` never runs"))
+                .await?;
+
+            return Ok(());
+        }
+        Err(e) => {
+            ctx.say(format!("The schedule `{schedule}` is not valid: {e}"))
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let mut tx = ctx.data().database.begin().await?;
+    let id = database::insert_airdrop(
+        &mut tx,
+        guild_id,
+        channel_id,
+        source,
+        amount,
+        &currency_id,
+        &serde_json::to_string(&action)?,
+        schedule,
+        next_run_at,
+        ctx.author().id,
+    )
+    .await?;
+
+    audit(
+        &mut tx,
+        ctx,
+        "addairdrop",
+        format!(
+            "#{id} {guild_id} {channel_id} {} {amount} {schedule} {action:?}",
+            pool.name
+        ),
+        None,
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    ctx.say(format!(
+        "Airdrop #{id} added, the first run is <t:{}:f>. Every run takes {amount} from the \
+        balance of pool `{}`",
+        next_run_at.timestamp(),
+        pool.name
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// List the recurring airdrops
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
+pub async fn airdrops(ctx: Context<'_>) -> Result<(), Error> {
+    let mut conn = ctx.data().database.acquire().await?;
+    let airdrops = database::get_airdrops(&mut conn, false).await?;
+
+    if airdrops.is_empty() {
+        ctx.say("There are no airdrops").await?;
+
+        return Ok(());
+    }
+
+    let lines = airdrops.iter().map(|airdrop| {
+        let action = match &airdrop.action {
+            ScheduledAction::Reactdrop { emoji, .. } => format!("reactdrop {emoji}"),
+            ScheduledAction::RoleTip { role_id, .. } => format!("tip to role {role_id}"),
+        };
+
+        format!(
+            "#{}: {} from {} in <#{}>, {action}, `{}`, next run <t:{}:f>",
+            airdrop.id,
+            airdrop.amount,
+            pool::mention(airdrop.source),
+            airdrop.channel_id,
+            airdrop.schedule,
+            airdrop.next_run_at.timestamp()
+        )
+    });
+
+    history::paginate(ctx, "Airdrops", &history::into_pages(lines)).await
+}
+
+/// The most runs `!airdropruns` shows.
+const AIRDROP_RUNS_SHOWN: i64 = 15;
+
+/// Show the most recent runs of an airdrop
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
+pub async fn airdropruns(ctx: Context<'_>, id: i64) -> Result<(), Error> {
+    let mut conn = ctx.data().database.acquire().await?;
+    let runs = database::get_airdrop_runs(&mut conn, id, AIRDROP_RUNS_SHOWN).await?;
+
+    if runs.is_empty() {
+        ctx.say(format!("Airdrop #{id} did not run yet")).await?;
+
+        return Ok(());
+    }
+
+    let lines = runs
+        .iter()
+        .map(|run| run.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(format!("Runs of airdrop #{id}"))
+                .description(lines),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Stop a recurring airdrop
+///
+/// Its runs stay in the history.
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_treasurer", prefix_command, hide_in_help)]
+pub async fn removeairdrop(ctx: Context<'_>, id: i64) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    if !database::disable_airdrop(&mut tx, id).await? {
+        ctx.say(format!("There is no airdrop #{id}")).await?;
+
+        return Ok(());
+    }
+
+    audit(&mut tx, ctx, "removeairdrop", format!("#{id}"), None, None).await?;
+
+    tx.commit().await?;
+
+    ctx.say(format!("Airdrop #{id} removed")).await?;
+
+    Ok(())
+}
+
+/// Give a user an admin role
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
+pub async fn grantrole(ctx: Context<'_>, user_id: UserId, role: AdminRole) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    let old_role = database::get_admin_role(&mut tx, user_id).await?;
+    database::set_admin_role(&mut tx, user_id, role, ctx.author().id).await?;
+
+    audit(
+        &mut tx,
+        ctx,
+        "grantrole",
+        format!("{user_id} {role}"),
+        old_role.map(|role| role.to_string()),
+        Some(role.to_string()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    ctx.send(CreateReply::default().content(format!("user {user_id} is now {role}")))
         .await?;
 
     Ok(())
 }
 
+/// Remove the admin role of a user
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_superadmin", prefix_command, hide_in_help)]
+pub async fn revokerole(ctx: Context<'_>, user_id: UserId) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(old_role) = database::get_admin_role(&mut tx, user_id).await? else {
+        ctx.send(
+            CreateReply::default().content(format!("user {user_id} has no granted admin role")),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    database::remove_admin_role(&mut tx, user_id).await?;
+
+    audit(
+        &mut tx,
+        ctx,
+        "revokerole",
+        user_id.to_string(),
+        Some(old_role.to_string()),
+        None,
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    ctx.send(CreateReply::default().content(format!("user {user_id} is no longer {old_role}")))
+        .await?;
+
+    Ok(())
+}
+
+/// The most entries a page of `!auditlog` shows.
+const AUDIT_LOG_PAGE_SIZE: usize = 15;
+
+/// Show the log of admin actions, newest first
+#[instrument(skip(ctx))]
+#[poise::command(dm_only, check = "is_support", prefix_command, hide_in_help)]
+pub async fn auditlog(ctx: Context<'_>) -> Result<(), Error> {
+    history::paginate_lines(
+        ctx,
+        "Audit log",
+        AUDIT_LOG_PAGE_SIZE,
+        "The audit log is empty",
+        |offset, limit| async move {
+            let mut conn = ctx.data().database.acquire().await?;
+            let entries = database::get_admin_audit(&mut conn, limit, offset).await?;
+
+            Ok(entries.iter().map(|entry| entry.to_string()).collect())
+        },
+    )
+    .await
+}
+
+#[derive(Debug)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub admin: UserId,
+    pub action: String,
+    pub arguments: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Display for AuditEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`#{}` <t:{}:f> <@{}> `!{} {}`",
+            self.id,
+            self.created_at.timestamp(),
+            self.admin,
+            self.action,
+            self.arguments
+        )?;
+
+        match (&self.old_value, &self.new_value) {
+            (Some(old), Some(new)) => write!(f, ": `{old}` → `{new}`"),
+            (None, Some(new)) => write!(f, ": `{new}`"),
+            (Some(old), None) => write!(f, ": `{old}` → _none_"),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+// Stores an admin action in the audit log and mirrors it to the admin thread, so that
+// every change to the bot's state can be traced back to the owner that made it.
+// Pass the transaction of the action itself: the entry, and the message to the admin
+// thread that goes out through the outbox, then stand or fall with the action.
+pub async fn audit(
+    conn: &mut PgConnection,
+    ctx: Context<'_>,
+    action: &str,
+    arguments: String,
+    old_value: Option<String>,
+    new_value: Option<String>,
+) -> Result<(), Error> {
+    let id = database::insert_admin_audit(
+        &mut *conn,
+        ctx.author().id,
+        action,
+        &arguments,
+        old_value.as_deref(),
+        new_value.as_deref(),
+    )
+    .await?;
+
+    let entry = AuditEntry {
+        id,
+        admin: ctx.author().id,
+        action: action.to_string(),
+        arguments,
+        old_value,
+        new_value,
+        created_at: Utc::now(),
+    };
+
+    trace!(?entry, "admin action audited");
+
+    notifier::queue(
+        conn,
+        Recipient::Channel(ctx.data().admin_thread()),
+        &CreateMessage::new()
+            .content(format!(":scroll: {entry}"))
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await
+}
+
+// The setters below are shared by the prefix commands and the `/admin` panel.
+// They return the value from before the change, to be stored in the audit log.
+
+async fn set_withdrawal_fee(data: &Data, fee: Amount) -> Amount {
+    let mut write = data.withdrawal_fee.write().await;
+    debug!("fee before changing: {:?}", *write);
+
+    std::mem::replace(&mut *write, fee)
+}
+
+async fn set_withdrawals_enabled(data: &Data, value: bool) -> bool {
+    let mut write = data.withdrawals_enabled.write().await;
+
+    std::mem::replace(&mut *write, value)
+}
+
+async fn set_deposits_enabled(data: &Data, value: bool) -> Result<bool, Error> {
+    let mut tx = data.database.begin().await?;
+    let mut write = data.deposits_enabled.write().await;
+    if *write && !value {
+        trace!("need to process possible unprocessed transactions");
+
+        let tx_proc = Arc::clone(&data.tx_processor);
+
+        process_stored_txids(&mut tx, tx_proc).await?
+    }
+    tx.commit().await?;
+
+    Ok(std::mem::replace(&mut *write, value))
+}
+
+async fn set_maintenance(data: &Data, value: bool) -> Result<bool, Error> {
+    let mut tx = data.database.begin().await?;
+    let mut write = data.tx_processor.maintenance.write().await;
+    if *write && !value {
+        trace!("need to process possible unprocessed transactions");
+
+        let tx_proc = Arc::clone(&data.tx_processor);
+
+        process_stored_txids(&mut tx, tx_proc).await?
+    }
+    tx.commit().await?;
+
+    Ok(std::mem::replace(&mut *write, value))
+}
+
 async fn process_stored_txids(
     tx: &mut Transaction<'_, Postgres>,
     tx_proc: Arc<TransactionProcessor>,
@@ -295,7 +1305,7 @@ async fn process_stored_txids(
 }
 
 #[instrument(skip(ctx))]
-#[poise::command(guild_only, owners_only, prefix_command, hide_in_help)]
+#[poise::command(guild_only, check = "is_support", prefix_command, hide_in_help)]
 pub async fn banned_balances(ctx: Context<'_>) -> Result<(), Error> {
     let guild = match ctx.guild_id() {
         Some(guild) => guild,
@@ -364,3 +1374,320 @@ pub async fn banned_balances(ctx: Context<'_>) -> Result<(), Error> {
 
     Ok(())
 }
+
+const PANEL_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, poise::Modal)]
+#[name = "Set withdrawal fee"]
+struct WithdrawalFeeModal {
+    #[name = "Withdrawal fee (in sats)"]
+    #[placeholder = "50000"]
+    fee: String,
+}
+
+/// Open the admin panel
+///
+/// Shows the state of the bot with buttons to change it, a form to set the withdrawal fee \
+/// and a menu to look up a user. Every action requires the same admin role as its prefix command.
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    hide_in_help,
+    default_member_permissions = "MANAGE_GUILD",
+    check = "is_support",
+    category = "Admin"
+)]
+pub async fn admin(ctx: Context<'_>) -> Result<(), Error> {
+    let ctx_id = ctx.id().to_string();
+
+    let reply_handle = ctx
+        .send(
+            CreateReply::default()
+                .ephemeral(true)
+                .embed(panel_embed(ctx.data()).await)
+                .components(panel_components(ctx.data(), &ctx_id).await),
+        )
+        .await?;
+
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .filter({
+            let ctx_id = ctx_id.clone();
+            move |press| press.data.custom_id.starts_with(&ctx_id)
+        })
+        .timeout(PANEL_TIMEOUT)
+        .await
+    {
+        let action = press.data.custom_id.trim_start_matches(&ctx_id);
+        trace!(action, "admin panel interaction");
+
+        match action {
+            "maintenance" | "deposits" | "withdrawals" => {
+                if !is_superadmin(ctx).await? {
+                    deny_panel_action(ctx, &press, AdminRole::Superadmin).await?;
+
+                    continue;
+                }
+
+                let mut conn = ctx.data().database.acquire().await?;
+
+                match action {
+                    "maintenance" => {
+                        let value = !*ctx.data().tx_processor.maintenance.read().await;
+                        let old_value = set_maintenance(ctx.data(), value).await?;
+                        audit(
+                            &mut conn,
+                            ctx,
+                            "maintenance",
+                            value.to_string(),
+                            Some(old_value.to_string()),
+                            Some(value.to_string()),
+                        )
+                        .await?;
+                    }
+                    "deposits" => {
+                        let value = !*ctx.data().deposits_enabled.read().await;
+                        let old_value = set_deposits_enabled(ctx.data(), value).await?;
+                        audit(
+                            &mut conn,
+                            ctx,
+                            "depositenabled",
+                            value.to_string(),
+                            Some(old_value.to_string()),
+                            Some(value.to_string()),
+                        )
+                        .await?;
+                    }
+                    _ => {
+                        let value = !*ctx.data().withdrawals_enabled.read().await;
+                        let old_value = set_withdrawals_enabled(ctx.data(), value).await;
+                        audit(
+                            &mut conn,
+                            ctx,
+                            "withdrawenabled",
+                            value.to_string(),
+                            Some(old_value.to_string()),
+                            Some(value.to_string()),
+                        )
+                        .await?;
+                    }
+                }
+                drop(conn);
+
+                press
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .embed(panel_embed(ctx.data()).await)
+                                .components(panel_components(ctx.data(), &ctx_id).await),
+                        ),
+                    )
+                    .await?;
+            }
+            "fee" => {
+                if !is_treasurer(ctx).await? {
+                    deny_panel_action(ctx, &press, AdminRole::Treasurer).await?;
+
+                    continue;
+                }
+
+                let defaults = WithdrawalFeeModal {
+                    fee: ctx.data().withdrawal_fee.read().await.as_sat().to_string(),
+                };
+
+                let Some(modal) = poise::execute_modal_on_component_interaction(
+                    ctx,
+                    press.clone(),
+                    Some(defaults),
+                    Some(Duration::from_secs(5 * 60)),
+                )
+                .await?
+                else {
+                    trace!("fee modal timed out");
+
+                    continue;
+                };
+
+                let Ok(fee) = modal.fee.trim().parse::<u64>() else {
+                    ctx.send(
+                        CreateReply::default()
+                            .ephemeral(true)
+                            .content(format!("`{}` is not a valid amount of sats", modal.fee)),
+                    )
+                    .await?;
+
+                    continue;
+                };
+
+                let old_fee = set_withdrawal_fee(ctx.data(), Amount::from_sat(fee)).await;
+                let mut conn = ctx.data().database.acquire().await?;
+                audit(
+                    &mut conn,
+                    ctx,
+                    "setwithdrawfee",
+                    fee.to_string(),
+                    Some(old_fee.as_sat().to_string()),
+                    Some(fee.to_string()),
+                )
+                .await?;
+
+                reply_handle
+                    .edit(
+                        ctx,
+                        CreateReply::default()
+                            .embed(panel_embed(ctx.data()).await)
+                            .components(panel_components(ctx.data(), &ctx_id).await),
+                    )
+                    .await?;
+            }
+            "user" => {
+                let ComponentInteractionDataKind::UserSelect { values } = &press.data.kind else {
+                    continue;
+                };
+
+                let Some(user_id) = values.first() else {
+                    continue;
+                };
+
+                press
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .ephemeral(true)
+                                .embed(user_lookup_embed(ctx, *user_id).await?),
+                        ),
+                    )
+                    .await?;
+            }
+            _ => continue,
+        }
+    }
+
+    // the buttons stop working after the timeout, so remove them
+    reply_handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .embed(panel_embed(ctx.data()).await)
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn panel_embed(data: &Data) -> CreateEmbed {
+    let maintenance = *data.tx_processor.maintenance.read().await;
+    let deposits_enabled = *data.deposits_enabled.read().await;
+    let withdrawals_enabled = *data.withdrawals_enabled.read().await;
+    let withdrawal_fee = *data.withdrawal_fee.read().await;
+
+    CreateEmbed::new()
+        .title("Admin panel")
+        .field("Maintenance", on_off(maintenance), true)
+        .field("Deposits", on_off(deposits_enabled), true)
+        .field("Withdrawals", on_off(withdrawals_enabled), true)
+        .field("Withdrawal fee", withdrawal_fee.to_string(), false)
+}
+
+async fn panel_components(data: &Data, ctx_id: &str) -> Vec<CreateActionRow> {
+    let maintenance = *data.tx_processor.maintenance.read().await;
+    let deposits_enabled = *data.deposits_enabled.read().await;
+    let withdrawals_enabled = *data.withdrawals_enabled.read().await;
+
+    let toggle = |action: &str, label: &str, value: bool| {
+        CreateButton::new(format!("{ctx_id}{action}"))
+            .label(format!("{label}: {}", on_off(value)))
+            .style(match value {
+                true => ButtonStyle::Success,
+                false => ButtonStyle::Danger,
+            })
+    };
+
+    vec![
+        CreateActionRow::Buttons(vec![
+            // maintenance on is the exceptional state, so it gets the red button
+            CreateButton::new(format!("{ctx_id}maintenance"))
+                .label(format!("Maintenance: {}", on_off(maintenance)))
+                .style(match maintenance {
+                    true => ButtonStyle::Danger,
+                    false => ButtonStyle::Secondary,
+                }),
+            toggle("deposits", "Deposits", deposits_enabled),
+            toggle("withdrawals", "Withdrawals", withdrawals_enabled),
+            CreateButton::new(format!("{ctx_id}fee"))
+                .label("Set withdrawal fee")
+                .style(ButtonStyle::Primary),
+        ]),
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                format!("{ctx_id}user"),
+                CreateSelectMenuKind::User {
+                    default_users: None,
+                },
+            )
+            .placeholder("Look up a user"),
+        ),
+    ]
+}
+
+async fn user_lookup_embed(ctx: Context<'_>, user_id: UserId) -> Result<CreateEmbed, Error> {
+    let mut conn = ctx.data().database.acquire().await?;
+    let currency_id = Address::from_str(VRSC_CURRENCY_ID)?;
+
+    let balance = Amount::from_sat(
+        database::get_balance_for_user(&mut conn, user_id, &currency_id)
+            .await?
+            .unwrap_or(0),
+    );
+    let blacklisted = database::get_blacklist_status(&mut conn, user_id)
+        .await?
+        .unwrap_or(false);
+    let address = database::get_address_from_user(&mut conn, &user_id, &currency_id).await?;
+    let role = admin_role(ctx, user_id).await?;
+
+    Ok(CreateEmbed::new()
+        .title(format!("User {user_id}"))
+        .description(format!("<@{user_id}>"))
+        .field("Balance", balance.to_string(), false)
+        .field(
+            "Deposit address",
+            address.map_or("_none_".to_string(), |address| address.to_string()),
+            false,
+        )
+        .field("Blacklisted", blacklisted.to_string(), true)
+        .field(
+            "Admin role",
+            role.map_or("_none_".to_string(), |role| role.to_string()),
+            true,
+        ))
+}
+
+async fn deny_panel_action(
+    ctx: Context<'_>,
+    press: &ComponentInteraction,
+    required: AdminRole,
+) -> Result<(), Error> {
+    press
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .ephemeral(true)
+                    .content(format!("You need to be {required} to do this")),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn on_off(value: bool) -> &'static str {
+    match value {
+        true => "on",
+        false => "off",
+    }
+}
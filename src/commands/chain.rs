@@ -263,11 +263,7 @@ pub async fn peerinfo(ctx: Context<'_>) -> Result<(), Error> {
 pub async fn price(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer().await?;
 
-    let resp: CoinPaprika =
-        reqwest::get("https://api.coinpaprika.com/v1/tickers/vrsc-verus-coin?quotes=USD,BTC")
-            .await?
-            .json()
-            .await?;
+    let resp = vrsc_ticker().await?;
 
     // let supply: f64 = reqwest::get("https://explorer.verus.io/ext/getmoneysupply")
     //     .await?
@@ -587,6 +583,16 @@ pub async fn pure(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// The current VRSC ticker, with quotes in USD and BTC.
+pub async fn vrsc_ticker() -> Result<CoinPaprika, Error> {
+    Ok(
+        reqwest::get("https://api.coinpaprika.com/v1/tickers/vrsc-verus-coin?quotes=USD,BTC")
+            .await?
+            .json()
+            .await?,
+    )
+}
+
 #[derive(Deserialize, Debug)]
 pub struct CoinPaprika {
     #[serde(rename = "id")]
@@ -0,0 +1,655 @@
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+use poise::{
+    ChoiceParameter, CreateReply,
+    serenity_prelude::{
+        ComponentInteractionCollector, CreateActionRow, CreateAttachment, CreateButton,
+        CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, UserId,
+    },
+};
+use serde::Serialize;
+use sqlx::types::chrono::{DateTime, Utc};
+use tracing::{instrument, trace, warn};
+use uuid::Uuid;
+use vrsc::{Address, Amount};
+
+use crate::{Context, Data, Error, database, pool};
+
+const HISTORY_PAGE_SIZE: usize = 10;
+/// The most characters Discord shows in the description of an embed.
+const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
+pub enum HistoryKind {
+    #[name = "Deposits"]
+    Deposit,
+    #[name = "Withdrawals"]
+    Withdrawal,
+    #[name = "Tips sent"]
+    TipSent,
+    #[name = "Tips received"]
+    TipReceived,
+    #[name = "Reactdrop payouts"]
+    Reactdrop,
+    #[name = "Adjustments"]
+    Adjustment,
+    #[name = "Held for reactdrops and scheduled tips"]
+    Held,
+}
+
+impl HistoryKind {
+    /// The kind as it is returned by `database::get_history`
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdraw",
+            Self::TipSent => "tip_sent",
+            Self::TipReceived => "tip_received",
+            Self::Reactdrop => "reactdrop",
+            Self::Adjustment => "adjustment",
+            Self::Held => "held",
+        }
+    }
+
+    pub fn from_sql(s: &str) -> Option<Self> {
+        match s {
+            "deposit" => Some(Self::Deposit),
+            "withdraw" => Some(Self::Withdrawal),
+            "tip_sent" => Some(Self::TipSent),
+            "tip_received" => Some(Self::TipReceived),
+            "reactdrop" => Some(Self::Reactdrop),
+            "adjustment" => Some(Self::Adjustment),
+            "held" => Some(Self::Held),
+            _ => None,
+        }
+    }
+}
+
+impl Display for HistoryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Deposit => write!(f, "Deposit"),
+            Self::Withdrawal => write!(f, "Withdrawal"),
+            Self::TipSent => write!(f, "Tip sent"),
+            Self::TipReceived => write!(f, "Tip received"),
+            Self::Reactdrop => write!(f, "Reactdrop"),
+            Self::Adjustment => write!(f, "Adjustment"),
+            Self::Held => write!(f, "Held"),
+        }
+    }
+}
+
+/// A single line in the history of a user.
+#[derive(Debug)]
+pub struct HistoryEntry {
+    pub kind: HistoryKind,
+    pub created_at: DateTime<Utc>,
+    /// In sats. Always positive, except for adjustments that debited the user.
+    pub amount: i64,
+    pub currency_id: Address,
+    /// The sender of a received tip, or the recipient of a sent tip to 1 user.
+    pub counterparty: Option<UserId>,
+    /// The number of users a tip was sent to.
+    pub recipients: i64,
+    pub txid: Option<String>,
+    pub fee: Option<Amount>,
+    /// The uuid of the transaction or tip event, or the reactdrop or scheduled tip an amount is
+    /// held for.
+    pub id: String,
+}
+
+impl HistoryEntry {
+    /// The amount as it affected the balance of the user.
+    pub fn signed_amount(&self) -> i64 {
+        match self.kind {
+            HistoryKind::Withdrawal | HistoryKind::TipSent | HistoryKind::Held => -self.amount,
+            _ => self.amount,
+        }
+    }
+
+    fn explorer_link(&self) -> Option<String> {
+        self.txid
+            .as_ref()
+            .map(|txid| format!("[explorer](https://insight.verus.io/tx/{txid})"))
+    }
+
+    pub fn to_line(&self, currency_name: &str) -> String {
+        let signed_amount = self.signed_amount();
+        let sign = if signed_amount < 0 { "-" } else { "+" };
+        let amount = Amount::from_sat(signed_amount.unsigned_abs());
+
+        let mut line = format!(
+            "<t:{}:d> **{}** {sign}{} {currency_name}",
+            self.created_at.timestamp(),
+            self.kind,
+            amount.to_string_in(vrsc::Denomination::Verus)
+        );
+
+        match self.kind {
+            HistoryKind::TipSent => match self.counterparty {
+                Some(counterparty) => {
+                    line.push_str(&format!(" to {}", pool::mention(counterparty)))
+                }
+                None => line.push_str(&format!(" to {} users", self.recipients)),
+            },
+            HistoryKind::TipReceived | HistoryKind::Reactdrop => {
+                if let Some(counterparty) = self.counterparty {
+                    line.push_str(&format!(" from {}", pool::mention(counterparty)));
+                }
+            }
+            HistoryKind::Withdrawal => {
+                if let Some(fee) = self.fee {
+                    line.push_str(&format!(" (fee {fee})"));
+                }
+            }
+            HistoryKind::Held => line.push_str(&format!(" for {}", self.id)),
+            HistoryKind::Deposit | HistoryKind::Adjustment => {}
+        }
+
+        if let Some(link) = self.explorer_link() {
+            line.push_str(&format!(" {link}"));
+        }
+
+        line
+    }
+}
+
+/// Show your transaction history
+///
+/// -------- :robot: **History** --------
+/// Shows your deposits, withdrawals, tips and reactdrop payouts, newest first, and what is held
+/// for your reactdrops and scheduled tips until they are paid out.
+/// Use the buttons below the message to go to the next or previous page.
+///
+/// Optionally filter by the kind of transaction or by currency.
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Wallet")]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "Only show this kind of transaction"] kind: Option<HistoryKind>,
+    #[description = "Only show this currency (e.g. VRSC)"] currency: Option<String>,
+) -> Result<(), Error> {
+    let currency_id = match currency.as_deref().map(|c| ctx.data().to_currency_id(c)) {
+        Some(Ok(currency_id)) => Some(currency_id),
+        Some(Err(_)) => {
+            ctx.send(CreateReply::default().ephemeral(true).content(format!(
+                "Currency `{}` not found",
+                currency.unwrap_or_default()
+            )))
+            .await?;
+
+            return Ok(());
+        }
+        None => None,
+    };
+
+    paginate_lines(
+        ctx,
+        "Your history",
+        HISTORY_PAGE_SIZE,
+        "There is nothing in your history yet",
+        |offset, limit| {
+            let currency_id = currency_id.clone();
+
+            async move {
+                let mut conn = ctx.data().database.acquire().await?;
+                let entries = database::get_history(
+                    &mut conn,
+                    ctx.author().id,
+                    kind,
+                    currency_id.as_ref(),
+                    Some(limit),
+                    offset,
+                )
+                .await?;
+                trace!("{} history entries", entries.len());
+
+                let currency_names = currency_names(ctx.data(), &entries);
+                Ok(entries
+                    .iter()
+                    .map(|entry| entry.to_line(&currency_names[&entry.currency_id]))
+                    .collect())
+            }
+        },
+    )
+    .await
+}
+
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+pub enum ExportFormat {
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+    #[name = "CSV and JSON"]
+    Both,
+}
+
+/// A history entry as it is written to an export file.
+#[derive(Debug, Serialize)]
+struct ExportRow {
+    timestamp: String,
+    kind: String,
+    amount: String,
+    currency: String,
+    currency_id: String,
+    counterparty: String,
+    recipients: i64,
+    fee: String,
+    txid: String,
+    id: String,
+}
+
+impl ExportRow {
+    const CSV_HEADER: &'static str =
+        "timestamp,kind,amount,currency,currency_id,counterparty,recipients,fee,txid,id";
+
+    fn new(entry: &HistoryEntry, currency_name: &str) -> Self {
+        let signed_amount = entry.signed_amount();
+        let sign = if signed_amount < 0 { "-" } else { "" };
+
+        ExportRow {
+            timestamp: entry.created_at.to_rfc3339(),
+            kind: entry.kind.to_string(),
+            amount: format!(
+                "{sign}{}",
+                Amount::from_sat(signed_amount.unsigned_abs())
+                    .to_string_in(vrsc::Denomination::Verus)
+            ),
+            currency: currency_name.to_string(),
+            currency_id: entry.currency_id.to_string(),
+            counterparty: entry
+                .counterparty
+                .map(|user_id| user_id.to_string())
+                .unwrap_or_default(),
+            recipients: entry.recipients,
+            fee: entry
+                .fee
+                .map(|fee| fee.to_string_in(vrsc::Denomination::Verus))
+                .unwrap_or_default(),
+            txid: entry.txid.clone().unwrap_or_default(),
+            id: entry.id.clone(),
+        }
+    }
+
+    fn to_csv_line(&self) -> String {
+        [
+            csv_field(&self.timestamp),
+            csv_field(&self.kind),
+            csv_field(&self.amount),
+            csv_field(&self.currency),
+            csv_field(&self.currency_id),
+            csv_field(&self.counterparty),
+            self.recipients.to_string(),
+            csv_field(&self.fee),
+            csv_field(&self.txid),
+            csv_field(&self.id),
+        ]
+        .join(",")
+    }
+}
+
+// Quotes a CSV field if it contains a separator, quote or newline.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Export your history as a file
+///
+/// -------- :robot: **Export** --------
+/// Sends you a DM with your full history of deposits, withdrawals, tips and reactdrop payouts \
+/// as a CSV and/or JSON file, e.g. for bookkeeping or taxes.
+///
+/// Make sure you allow DMs from this server.
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Wallet")]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "The file format, CSV by default"] format: Option<ExportFormat>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let entries = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_history(&mut conn, ctx.author().id, None, None, None, 0).await?
+    };
+
+    if entries.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("There is nothing in your history to export"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let currency_names = currency_names(ctx.data(), &entries);
+    let rows = entries
+        .iter()
+        .map(|entry| ExportRow::new(entry, &currency_names[&entry.currency_id]))
+        .collect::<Vec<_>>();
+
+    let filename = format!(
+        "verusbot-history-{}-{}",
+        ctx.author().id,
+        Utc::now().format("%Y%m%d")
+    );
+    let mut message = CreateMessage::new().content(format!(
+        "Here is your history of {} transactions.",
+        rows.len()
+    ));
+
+    let format = format.unwrap_or(ExportFormat::Csv);
+    if let ExportFormat::Csv | ExportFormat::Both = format {
+        let csv = std::iter::once(ExportRow::CSV_HEADER.to_string())
+            .chain(rows.iter().map(|row| row.to_csv_line()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        message = message.add_file(CreateAttachment::bytes(
+            csv.into_bytes(),
+            format!("{filename}.csv"),
+        ));
+    }
+    if let ExportFormat::Json | ExportFormat::Both = format {
+        message = message.add_file(CreateAttachment::bytes(
+            serde_json::to_vec_pretty(&rows)?,
+            format!("{filename}.json"),
+        ));
+    }
+
+    let response = match ctx.author().direct_message(ctx, message).await {
+        Ok(_) => "Your history has been sent to you in a DM".to_string(),
+        Err(e) => {
+            warn!("could not DM export to {}: {e}", ctx.author().id);
+
+            "I couldn't send you a DM. Please allow DMs from this server and try again".to_string()
+        }
+    };
+
+    ctx.send(CreateReply::default().ephemeral(true).content(response))
+        .await?;
+
+    Ok(())
+}
+
+/// Looks up the names of all the currencies in `entries`, falling back to the currency id.
+pub fn currency_names(data: &Data, entries: &[HistoryEntry]) -> HashMap<Address, String> {
+    let mut names = HashMap::new();
+
+    for entry in entries {
+        if !names.contains_key(&entry.currency_id) {
+            let name = data
+                .to_currency_name(&entry.currency_id)
+                .unwrap_or_else(|_| entry.currency_id.to_string());
+            names.insert(entry.currency_id.clone(), name);
+        }
+    }
+
+    names
+}
+
+/// Joins lines into pages that fit in the description of an embed. A line that doesn't fit in a
+/// page by itself is cut off.
+pub fn into_pages(lines: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut pages = vec![];
+    let mut page = String::new();
+
+    for line in lines {
+        let line = line
+            .chars()
+            .take(EMBED_DESCRIPTION_LIMIT)
+            .collect::<String>();
+
+        if !page.is_empty()
+            && page.chars().count() + 1 + line.chars().count() > EMBED_DESCRIPTION_LIMIT
+        {
+            pages.push(std::mem::take(&mut page));
+        }
+        if !page.is_empty() {
+            page.push('\n');
+        }
+        page.push_str(&line);
+    }
+
+    if !page.is_empty() {
+        pages.push(page);
+    }
+
+    pages
+}
+
+/// Joins the lines of a page of at most `per_page` lines, cutting off lines that are too long
+/// for all of them to fit in the description of an embed.
+fn join_page(lines: &[String], per_page: usize) -> String {
+    // leaves room for the newlines between the lines
+    let line_limit = EMBED_DESCRIPTION_LIMIT / per_page.max(1) - 1;
+
+    lines
+        .iter()
+        .map(|line| line.chars().take(line_limit).collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Shows the pages in an ephemeral embed with previous and next buttons.
+// Only the author of the command can use the buttons, they stop working after 5 minutes.
+pub async fn paginate(ctx: Context<'_>, title: &str, pages: &[String]) -> Result<(), Error> {
+    let page = |page: usize| (pages[page].clone(), page + 1 < pages.len());
+
+    show_pages(ctx, title, Some(pages.len()), page(0), |page_index| {
+        std::future::ready(Ok(page(page_index)))
+    })
+    .await
+}
+
+/// Like `paginate`, for lists that are too long to load at once. `load` gets the offset and the
+/// limit of the lines of a page and is only called for the page that is shown. Shows `empty`
+/// when there are no lines at all.
+pub async fn paginate_lines<F, Fut>(
+    ctx: Context<'_>,
+    title: &str,
+    per_page: usize,
+    empty: &str,
+    load: F,
+) -> Result<(), Error>
+where
+    F: Fn(i64, i64) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<String>, Error>> + Send,
+{
+    // one line more than fits on the page tells whether there is a next page
+    let load_page = |page: usize| {
+        let lines = load((page * per_page) as i64, per_page as i64 + 1);
+
+        async move {
+            let mut lines = lines.await?;
+            let has_next = lines.len() > per_page;
+            lines.truncate(per_page);
+
+            Ok((join_page(&lines, per_page), has_next))
+        }
+    };
+
+    let first_page = load_page(0).await?;
+    if first_page.0.is_empty() {
+        ctx.send(CreateReply::default().ephemeral(true).content(empty))
+            .await?;
+
+        return Ok(());
+    }
+
+    show_pages(ctx, title, None, first_page, load_page).await
+}
+
+// `first_page` and `load_page` give the description of a page and whether there is a page after
+// it.
+async fn show_pages<F, Fut>(
+    ctx: Context<'_>,
+    title: &str,
+    page_count: Option<usize>,
+    first_page: (String, bool),
+    load_page: F,
+) -> Result<(), Error>
+where
+    F: Fn(usize) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<(String, bool), Error>> + Send,
+{
+    let ctx_id = ctx.id().to_string();
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let page_embed = |page: usize, description: &str| {
+        let footer = match page_count {
+            Some(page_count) => format!("Page {}/{page_count}", page + 1),
+            None => format!("Page {}", page + 1),
+        };
+
+        CreateEmbed::new()
+            .title(title)
+            .description(description)
+            .footer(CreateEmbedFooter::new(footer))
+    };
+    let buttons = |page: usize, has_next: bool| {
+        vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_button_id)
+                .emoji('◀')
+                .disabled(page == 0),
+            CreateButton::new(&next_button_id)
+                .emoji('▶')
+                .disabled(!has_next),
+        ])]
+    };
+
+    let mut current_page = 0;
+    let (mut description, mut has_next) = first_page;
+
+    let reply_handle = ctx
+        .send(
+            CreateReply::default()
+                .ephemeral(true)
+                .embed(page_embed(current_page, &description))
+                .components(if has_next {
+                    buttons(current_page, has_next)
+                } else {
+                    vec![]
+                }),
+        )
+        .await?;
+
+    if !has_next {
+        return Ok(());
+    }
+
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .filter({
+            let ctx_id = ctx_id.clone();
+            move |press| press.data.custom_id.starts_with(&ctx_id)
+        })
+        .timeout(Duration::from_secs(5 * 60))
+        .await
+    {
+        if press.data.custom_id == next_button_id && has_next {
+            current_page += 1;
+        } else if press.data.custom_id == prev_button_id {
+            current_page = current_page.saturating_sub(1);
+        } else {
+            continue;
+        }
+
+        (description, has_next) = load_page(current_page).await?;
+
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(page_embed(current_page, &description))
+                        .components(buttons(current_page, has_next)),
+                ),
+            )
+            .await?;
+    }
+
+    reply_handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .embed(page_embed(current_page, &description))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_field_plain() {
+        assert_eq!(csv_field("VRSC"), "VRSC");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_field_quoted() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn pages_fit_in_an_embed() {
+        let line = "x".repeat(1000);
+        let pages = into_pages(std::iter::repeat_n(line, 9));
+
+        // four lines and their newlines fit in a page
+        assert_eq!(pages.len(), 3);
+        assert!(
+            pages
+                .iter()
+                .all(|page| page.chars().count() <= EMBED_DESCRIPTION_LIMIT)
+        );
+        assert_eq!(pages[2].chars().count(), 1000);
+
+        assert_eq!(
+            into_pages(["x".repeat(5000)])[0].len(),
+            EMBED_DESCRIPTION_LIMIT
+        );
+        assert!(into_pages(Vec::<String>::new()).is_empty());
+    }
+
+    #[test]
+    fn lines_fit_in_a_page() {
+        let lines = vec!["x".repeat(1000); 15];
+        let page = join_page(&lines, 15);
+
+        assert!(page.chars().count() <= EMBED_DESCRIPTION_LIMIT);
+        assert_eq!(page.lines().count(), 15);
+        assert_eq!(join_page(&["short".to_string()], 15), "short");
+    }
+
+    #[test]
+    fn kind_roundtrip() {
+        for kind in [
+            HistoryKind::Deposit,
+            HistoryKind::Withdrawal,
+            HistoryKind::TipSent,
+            HistoryKind::TipReceived,
+            HistoryKind::Reactdrop,
+            HistoryKind::Adjustment,
+            HistoryKind::Held,
+        ] {
+            assert_eq!(HistoryKind::from_sql(kind.as_sql()), Some(kind));
+        }
+    }
+}
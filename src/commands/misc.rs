@@ -1,8 +1,15 @@
-use std::{fmt::Display, time::Instant};
+use std::{
+    fmt::Display,
+    time::{Duration, Instant},
+};
 
 use poise::{
     ChoiceParameter, CreateReply,
-    serenity_prelude::{CreateEmbed, CreateEmbedFooter},
+    serenity_prelude::{
+        ComponentInteractionCollector, ComponentInteractionDataKind, CreateActionRow, CreateEmbed,
+        CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+    },
 };
 use tracing::instrument;
 use uuid::Uuid;
@@ -102,10 +109,22 @@ pub async fn register(ctx: Context<'_>, #[flag] global: bool) -> Result<(), Erro
 /// - **Channel only**: Do not get DM's about tips, only get notifications of \
 /// direct tips in channels where you get tipped directly.
 /// - **Off**: Do not get notifications of any kind.
+///
+/// Run without a setting to open a panel where you can choose a setting per type of event, \
+/// like deposits, direct tips, reactdrop wins or price alerts.
 #[instrument(skip(ctx, notifications), fields(request_id = %Uuid::new_v4() ))]
 #[poise::command(track_edits, slash_command, category = "Miscellaneous")]
-pub async fn notifications(ctx: Context<'_>, notifications: Notification) -> Result<(), Error> {
+pub async fn notifications(
+    ctx: Context<'_>,
+    #[description = "The setting for all tips. Leave empty to open the settings panel"]
+    notifications: Option<Notification>,
+) -> Result<(), Error> {
+    let Some(notifications) = notifications else {
+        return notifications_panel(ctx).await;
+    };
+
     let mut conn = ctx.data().database.acquire().await?;
+
     database::update_notifications(&mut conn, &ctx.author().id, &notifications.to_string()).await?;
 
     ctx.send(CreateReply::default().ephemeral(true).content(format!(
@@ -117,7 +136,160 @@ pub async fn notifications(ctx: Context<'_>, notifications: Notification) -> Res
     Ok(())
 }
 
-#[derive(Debug, ChoiceParameter)]
+const PANEL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+async fn notifications_panel(ctx: Context<'_>) -> Result<(), Error> {
+    let ctx_id = ctx.id().to_string();
+    // `None` is the general setting, which applies to all tips that have no setting of their own.
+    let mut selected: Option<NotificationEvent> = None;
+
+    // a connection is only held while handling a press, not while waiting for the next one.
+    let settings = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_notification_settings(&mut conn, ctx.author().id).await?
+    };
+
+    let reply_handle = ctx
+        .send(
+            CreateReply::default()
+                .ephemeral(true)
+                .embed(settings_embed(&settings))
+                .components(settings_components(&ctx_id, selected)),
+        )
+        .await?;
+
+    while let Some(press) = ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .filter({
+            let ctx_id = ctx_id.clone();
+            move |press| press.data.custom_id.starts_with(&ctx_id)
+        })
+        .timeout(PANEL_TIMEOUT)
+        .await
+    {
+        let ComponentInteractionDataKind::StringSelect { values } = &press.data.kind else {
+            continue;
+        };
+        let Some(value) = values.first() else {
+            continue;
+        };
+
+        let mut conn = ctx.data().database.acquire().await?;
+
+        match press.data.custom_id.trim_start_matches(&ctx_id) {
+            "event" => {
+                selected = NotificationEvent::ALL
+                    .into_iter()
+                    .find(|event| event.column() == value);
+            }
+            "setting" => {
+                let setting = match value.as_str() {
+                    "default" => None,
+                    setting => Some(Notification::from(setting.to_string())),
+                };
+
+                database::update_event_notification(
+                    &mut conn,
+                    ctx.author().id,
+                    selected,
+                    setting.as_ref(),
+                )
+                .await?;
+            }
+            _ => continue,
+        }
+
+        let settings = database::get_notification_settings(&mut conn, ctx.author().id).await?;
+        drop(conn);
+
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(settings_embed(&settings))
+                        .components(settings_components(&ctx_id, selected)),
+                ),
+            )
+            .await?;
+    }
+
+    // the select menus stop working after the timeout, so remove them
+    let settings = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_notification_settings(&mut conn, ctx.author().id).await?
+    };
+    reply_handle
+        .edit(
+            ctx,
+            CreateReply::default()
+                .embed(settings_embed(&settings))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn settings_embed(settings: &NotificationSettings) -> CreateEmbed {
+    let mut embed = CreateEmbed::new().title("Notification settings").field(
+        "All tips",
+        settings
+            .general
+            .map(|setting| setting.to_string())
+            .unwrap_or_else(|| format!("Default ({})", Notification::ChannelOnly)),
+        false,
+    );
+
+    for event in NotificationEvent::ALL {
+        let value = match settings.get(event) {
+            Some(setting) => setting.to_string(),
+            None => format!("Default ({})", settings.for_event(event)),
+        };
+        embed = embed.field(event.name(), value, true);
+    }
+
+    embed.footer(CreateEmbedFooter::new(
+        "Pick an event in the first menu, then its setting in the second menu.",
+    ))
+}
+
+fn settings_components(ctx_id: &str, selected: Option<NotificationEvent>) -> Vec<CreateActionRow> {
+    let mut events = vec![
+        CreateSelectMenuOption::new("All tips", "general").default_selection(selected.is_none()),
+    ];
+    events.extend(NotificationEvent::ALL.into_iter().map(|event| {
+        CreateSelectMenuOption::new(event.name(), event.column())
+            .default_selection(selected == Some(event))
+    }));
+
+    let mut settings = [
+        Notification::All,
+        Notification::DMOnly,
+        Notification::ChannelOnly,
+        Notification::Off,
+    ]
+    .into_iter()
+    .map(|setting| CreateSelectMenuOption::new(setting.to_string(), setting.to_string()))
+    .collect::<Vec<_>>();
+    settings.push(CreateSelectMenuOption::new("Use the default", "default"));
+
+    vec![
+        CreateActionRow::SelectMenu(CreateSelectMenu::new(
+            format!("{ctx_id}event"),
+            CreateSelectMenuKind::String { options: events },
+        )),
+        CreateActionRow::SelectMenu(
+            CreateSelectMenu::new(
+                format!("{ctx_id}setting"),
+                CreateSelectMenuKind::String { options: settings },
+            )
+            .placeholder("Choose a setting"),
+        ),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ChoiceParameter)]
 pub enum Notification {
     #[name = "All"]
     All,
@@ -129,6 +301,16 @@ pub enum Notification {
     Off,
 }
 
+impl Notification {
+    pub fn wants_dm(&self) -> bool {
+        matches!(self, Self::All | Self::DMOnly)
+    }
+
+    pub fn wants_mention(&self) -> bool {
+        matches!(self, Self::All | Self::ChannelOnly)
+    }
+}
+
 impl From<String> for Notification {
     fn from(s: String) -> Self {
         match s.as_str() {
@@ -151,3 +333,93 @@ impl Display for Notification {
         }
     }
 }
+
+/// The kinds of events a user can be notified of, each with its own setting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationEvent {
+    DepositConfirmed,
+    WithdrawalSent,
+    DirectTip,
+    /// Tips to a role, rain.
+    GroupTip,
+    ReactdropWin,
+    /// The price of VRSC moved by more than `price_alert_percent`, only sent in DM.
+    PriceAlert,
+}
+
+impl NotificationEvent {
+    pub const ALL: [Self; 6] = [
+        Self::DepositConfirmed,
+        Self::WithdrawalSent,
+        Self::DirectTip,
+        Self::GroupTip,
+        Self::ReactdropWin,
+        Self::PriceAlert,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DepositConfirmed => "Deposit confirmed",
+            Self::WithdrawalSent => "Withdrawal sent",
+            Self::DirectTip => "Direct tip",
+            Self::GroupTip => "Role tip or rain",
+            Self::ReactdropWin => "Reactdrop win",
+            Self::PriceAlert => "Price alert",
+        }
+    }
+
+    /// The column in the `notifications` table that holds the setting for this event.
+    pub fn column(&self) -> &'static str {
+        match self {
+            Self::DepositConfirmed => "deposit_confirmed",
+            Self::WithdrawalSent => "withdrawal_sent",
+            Self::DirectTip => "direct_tip",
+            Self::GroupTip => "group_tip",
+            Self::ReactdropWin => "reactdrop_win",
+            Self::PriceAlert => "price_alert",
+        }
+    }
+}
+
+/// The notification settings of a user. Any of them can be unset, in which case a default applies.
+#[derive(Debug, Default)]
+pub struct NotificationSettings {
+    /// The setting for all tips, set with `/notifications <setting>`.
+    pub general: Option<Notification>,
+    pub deposit_confirmed: Option<Notification>,
+    pub withdrawal_sent: Option<Notification>,
+    pub direct_tip: Option<Notification>,
+    pub group_tip: Option<Notification>,
+    pub reactdrop_win: Option<Notification>,
+    pub price_alert: Option<Notification>,
+}
+
+impl NotificationSettings {
+    /// The setting the user chose for `event`, if any.
+    pub fn get(&self, event: NotificationEvent) -> Option<Notification> {
+        match event {
+            NotificationEvent::DepositConfirmed => self.deposit_confirmed,
+            NotificationEvent::WithdrawalSent => self.withdrawal_sent,
+            NotificationEvent::DirectTip => self.direct_tip,
+            NotificationEvent::GroupTip => self.group_tip,
+            NotificationEvent::ReactdropWin => self.reactdrop_win,
+            NotificationEvent::PriceAlert => self.price_alert,
+        }
+    }
+
+    /// The setting that applies to `event`. Tips fall back to the general setting; deposits are
+    /// sent in DM, withdrawals and price alerts are not, unless the user says otherwise.
+    pub fn for_event(&self, event: NotificationEvent) -> Notification {
+        if let Some(setting) = self.get(event) {
+            return setting;
+        }
+
+        match event {
+            NotificationEvent::DepositConfirmed => Notification::DMOnly,
+            NotificationEvent::WithdrawalSent | NotificationEvent::PriceAlert => Notification::Off,
+            NotificationEvent::DirectTip
+            | NotificationEvent::GroupTip
+            | NotificationEvent::ReactdropWin => self.general.unwrap_or(Notification::ChannelOnly),
+        }
+    }
+}
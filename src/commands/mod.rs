@@ -5,7 +5,12 @@ use crate::{Context, Error};
 
 pub mod admin;
 pub mod chain;
+pub mod history;
 pub mod misc;
+pub mod pool;
+pub mod reactdrop;
+pub mod scheduled;
+pub mod stats;
 pub mod tipping;
 pub mod wallet;
 
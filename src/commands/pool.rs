@@ -0,0 +1,440 @@
+use std::str::FromStr;
+
+use poise::{
+    CreateReply,
+    serenity_prelude::{CreateEmbed, Role, UserId},
+};
+use sqlx::PgConnection;
+use tracing::*;
+use uuid::Uuid;
+use vrsc::{Address, Amount};
+use vrsc_rpc::client::RpcApi;
+
+use crate::{
+    Context, Error, VRSC_CURRENCY_ID,
+    commands::{
+        admin::{audit, is_treasurer},
+        history::currency_names,
+        user_blacklisted,
+    },
+    database,
+    pool::{self, Pool},
+    wallet::{get_and_check_balance, send_deposit_address_msg},
+};
+
+/// The most entries `/pool activity` shows.
+const POOL_ACTIVITY_SHOWN: i64 = 15;
+
+/// Shared accounts of this server, like a treasury or a prize pool
+///
+/// -------- :robot: **Pools** --------
+/// A pool has its own balance and deposit address. Anyone can donate to a pool, but only \
+/// members with the spender role of the pool can spend from it, by picking the pool in \
+/// `/tip user`, `/tip role` or `/reactdrop start`.
+#[instrument(skip(_ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Pools",
+    subcommands("create", "spenders", "list", "donate", "deposit", "activity")
+)]
+pub async fn pool(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create a pool in this server
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, check = "is_treasurer", category = "Pools")]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "The name of the pool, e.g. treasury"]
+    #[max_length = 50]
+    name: String,
+    #[description = "Members with this role can spend from the pool"] spender_role: Option<Role>,
+) -> Result<(), Error> {
+    let name = name.trim();
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(pool) = database::insert_pool(
+        &mut tx,
+        ctx.guild_id().unwrap(),
+        name,
+        spender_role.as_ref().map(|role| role.id),
+        ctx.author().id,
+    )
+    .await?
+    else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("There already is a pool named `{name}`")),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    debug!("pool created: {pool:?}");
+
+    audit(
+        &mut tx,
+        ctx,
+        "pool create",
+        format!("#{} {name}", pool.id),
+        None,
+        pool.spender_role.map(|role_id| role_id.to_string()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "Pool `{name}` created. Use `/pool deposit` to fund it."
+    )))
+    .await?;
+
+    Ok(())
+}
+
+/// Change who can spend from a pool
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, check = "is_treasurer", category = "Pools")]
+async fn spenders(
+    ctx: Context<'_>,
+    #[description = "The name of the pool"] name: String,
+    #[description = "Members with this role can spend from the pool. Leave empty for treasurers only"]
+    spender_role: Option<Role>,
+) -> Result<(), Error> {
+    let Some(pool) = find_pool(ctx, &name).await? else {
+        return Ok(());
+    };
+
+    let spender_role = spender_role.map(|role| role.id);
+    let mut tx = ctx.data().database.begin().await?;
+    database::set_pool_spender_role(&mut tx, pool.id, spender_role).await?;
+
+    audit(
+        &mut tx,
+        ctx,
+        "pool spenders",
+        format!("#{} {}", pool.id, pool.name),
+        pool.spender_role.map(|role_id| role_id.to_string()),
+        spender_role.map(|role_id| role_id.to_string()),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    let content = match spender_role {
+        Some(role_id) => format!(
+            "Members with the <@&{role_id}> role can now spend from pool `{}`",
+            pool.name
+        ),
+        None => format!("Only treasurers can now spend from pool `{}`", pool.name),
+    };
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// Show the pools of this server and their balance
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Pools")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut conn = ctx.data().database.acquire().await?;
+    let pools = database::get_pools(
+        &mut conn,
+        ctx.guild_id().unwrap(),
+        &Address::from_str(VRSC_CURRENCY_ID)?,
+    )
+    .await?;
+
+    if pools.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("This server has no pools"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let lines = pools
+        .iter()
+        .map(|(pool, balance)| {
+            let spenders = match pool.spender_role {
+                Some(role_id) => format!("<@&{role_id}>"),
+                None => String::from("treasurers"),
+            };
+
+            format!("**{}**: {balance}, spent by {spenders}", pool.name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        CreateReply::default()
+            .ephemeral(true)
+            .embed(CreateEmbed::new().title("Pools").description(lines)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Give part of your balance to a pool
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Pools")]
+async fn donate(
+    ctx: Context<'_>,
+    #[description = "The name of the pool"] name: String,
+    #[description = "The amount you want to donate"]
+    #[min = 0.1]
+    amount: f64,
+    #[description = "An optional message to go with your donation"]
+    #[max_length = 200]
+    note: Option<String>,
+) -> Result<(), Error> {
+    if user_blacklisted(ctx, ctx.author().id).await? {
+        return Ok(());
+    }
+
+    let Some(pool) = find_pool(ctx, &name).await? else {
+        return Ok(());
+    };
+
+    let amount = Amount::from_vrsc(amount)?;
+
+    if get_and_check_balance(&ctx, amount, Amount::ZERO)
+        .await?
+        .is_none()
+    {
+        return Ok(());
+    }
+
+    let currency_id = Address::from_str(VRSC_CURRENCY_ID)?;
+    let donation = [(pool.account(), amount)];
+
+    let mut tx = ctx.data().database.begin().await?;
+    database::process_a_tip(&mut tx, ctx.author().id, &donation, &currency_id).await?;
+    database::store_tip_transactions(
+        &mut tx,
+        &Uuid::new_v4(),
+        &donation,
+        "donation",
+        ctx.author().id,
+        &currency_id,
+        ctx.guild_id(),
+        note.as_deref(),
+    )
+    .await?;
+    tx.commit().await?;
+
+    ctx.send(CreateReply::default().content(format!(
+        "<@{}> donated {amount} to pool **{}**!",
+        ctx.author().id,
+        pool.name
+    )))
+    .await?;
+
+    Ok(())
+}
+
+/// Get the deposit address of a pool
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Pools")]
+async fn deposit(
+    ctx: Context<'_>,
+    #[description = "The name of the pool"] name: String,
+) -> Result<(), Error> {
+    let Some(pool) = find_pool(ctx, &name).await? else {
+        return Ok(());
+    };
+
+    let currency_id = Address::from_str(VRSC_CURRENCY_ID)?;
+    let mut tx = ctx.data().database.begin().await?;
+
+    let address = match database::get_address_from_user(&mut tx, &pool.account(), &currency_id)
+        .await?
+    {
+        Some(address) => address,
+        None => {
+            let address = ctx.data().verus()?.get_new_address()?;
+            database::store_new_address_for_user(&mut tx, &pool.account(), &address, &currency_id)
+                .await?;
+
+            address
+        }
+    };
+
+    tx.commit().await?;
+
+    send_deposit_address_msg(ctx, &address).await?;
+
+    Ok(())
+}
+
+/// Show the most recent activity of a pool
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Pools")]
+async fn activity(
+    ctx: Context<'_>,
+    #[description = "The name of the pool"] name: String,
+) -> Result<(), Error> {
+    let Some(pool) = find_pool(ctx, &name).await? else {
+        return Ok(());
+    };
+
+    let entries = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_history(
+            &mut conn,
+            pool.account(),
+            None,
+            None,
+            Some(POOL_ACTIVITY_SHOWN),
+            0,
+        )
+        .await?
+    };
+
+    if entries.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("Pool `{}` has no activity yet", pool.name)),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let currency_names = currency_names(ctx.data(), &entries);
+    let lines = entries
+        .iter()
+        .map(|entry| entry.to_line(&currency_names[&entry.currency_id]))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(
+        CreateReply::default().ephemeral(true).embed(
+            CreateEmbed::new()
+                .title(format!("Activity of pool {}", pool.name))
+                .description(lines),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The account a tip or reactdrop is paid from: the author's own, or the account of the pool
+/// when one is picked and the author may spend from it. Otherwise the author is told why, and
+/// `None` is returned.
+pub async fn paying_account(ctx: Context<'_>, pool: Option<&str>) -> Result<Option<UserId>, Error> {
+    let Some(name) = pool else {
+        return Ok(Some(ctx.author().id));
+    };
+
+    let Some(pool) = find_pool(ctx, name).await? else {
+        return Ok(None);
+    };
+
+    if !may_spend(ctx, &pool).await? {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("You can't spend from pool `{}`", pool.name)),
+        )
+        .await?;
+
+        return Ok(None);
+    }
+
+    Ok(Some(pool.account()))
+}
+
+/// Records in the audit log who spent from a pool, as the tips and reactdrops it paid for only
+/// show the pool. Does nothing for the account of a user.
+pub async fn audit_spend(
+    conn: &mut PgConnection,
+    ctx: Context<'_>,
+    account: UserId,
+    spent_on: String,
+) -> Result<(), Error> {
+    let Some(id) = pool::pool_id(account) else {
+        return Ok(());
+    };
+
+    audit(
+        conn,
+        ctx,
+        "pool spend",
+        format!("pool #{id}: {spent_on}"),
+        None,
+        None,
+    )
+    .await
+}
+
+/// Whether the author can spend from the pool that `account` belongs to. Always false for the
+/// account of a user.
+pub async fn may_spend_from(ctx: Context<'_>, account: UserId) -> Result<bool, Error> {
+    let Some(id) = pool::pool_id(account) else {
+        return Ok(false);
+    };
+
+    let pool = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_pool_by_id(&mut conn, id).await?
+    };
+
+    match pool {
+        Some(pool) => may_spend(ctx, &pool).await,
+        None => Ok(false),
+    }
+}
+
+async fn may_spend(ctx: Context<'_>, pool: &Pool) -> Result<bool, Error> {
+    if ctx.guild_id() != Some(pool.guild_id) {
+        return Ok(false);
+    }
+
+    let has_role = match (pool.spender_role, ctx.author_member().await) {
+        (Some(role_id), Some(member)) => member.roles.contains(&role_id),
+        _ => false,
+    };
+
+    Ok(has_role || is_treasurer(ctx).await?)
+}
+
+async fn find_pool(ctx: Context<'_>, name: &str) -> Result<Option<Pool>, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("Pools can only be used in a server"),
+        )
+        .await?;
+
+        return Ok(None);
+    };
+
+    let pool = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_pool(&mut conn, guild_id, name.trim()).await?
+    };
+
+    if pool.is_none() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("There is no pool named `{}`", name.trim())),
+        )
+        .await?;
+    }
+
+    Ok(pool)
+}
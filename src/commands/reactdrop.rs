@@ -0,0 +1,673 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        CreateAllowedMentions, CreateEmbed, CreateEmbedFooter, GuildId, ReactionType, Role, UserId,
+    },
+};
+use sqlx::PgConnection;
+use tracing::*;
+use uuid::Uuid;
+use vrsc::{Address, Amount};
+use vrsc_rpc::client::RpcApi;
+
+use crate::{
+    Context, Error, VRSC_CURRENCY_ID,
+    commands::{
+        admin::{audit, is_support},
+        pool::{audit_spend, may_spend_from, paying_account},
+        user_blacklisted,
+    },
+    database, pool,
+    reactdrop::{self, Reactdrop, ReactdropState, Requirements},
+    scheduler::{self, ScheduledAction},
+    wallet::get_and_check_balance_of,
+};
+
+/// The most reactdrops `/reactdrop list` shows.
+const MAX_LISTED_REACTDROPS: usize = 20;
+
+/// The shortest a reactdrop can run, so people have a chance to react.
+const MIN_DURATION: Duration = Duration::seconds(30);
+/// The longest a reactdrop can run, and the furthest into the future a tip can be scheduled.
+const MAX_DURATION: Duration = Duration::days(30);
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum Hms {
+    Days,
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+impl Hms {
+    /// Returns `None` when the duration is out of range. `time` can be anything a user enters.
+    pub fn duration(&self, time: i64) -> Option<Duration> {
+        let seconds = match self {
+            Hms::Days => time.checked_mul(24 * 60 * 60),
+            Hms::Hours => time.checked_mul(60 * 60),
+            Hms::Minutes => time.checked_mul(60),
+            Hms::Seconds => Some(time),
+        }?;
+
+        Duration::try_seconds(seconds).filter(|duration| *duration <= MAX_DURATION)
+    }
+}
+
+/// Turns the optional delay of a tip into the time it should happen, or an explanation for the
+/// user of why it can't be scheduled.
+pub fn scheduled_time(
+    delay: Option<i64>,
+    delay_unit: Option<Hms>,
+    guild_id: Option<GuildId>,
+    now: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, &'static str> {
+    let Some(delay) = delay else {
+        return Ok(None);
+    };
+
+    if guild_id.is_none() {
+        return Err("Tips can only be scheduled in a server");
+    }
+
+    delay_unit
+        .unwrap_or(Hms::Minutes)
+        .duration(delay)
+        .map(|delay| Some(now + delay))
+        .ok_or("A tip can be scheduled at most 30 days ahead")
+}
+
+/// The message that announces a reactdrop, and which people react to.
+pub fn announcement(
+    author: UserId,
+    amount: Amount,
+    reaction_type: &ReactionType,
+    winners: Option<u32>,
+    draw_height: Option<u64>,
+    requirements: &Requirements,
+    finish_time: DateTime<Utc>,
+) -> String {
+    let giveaway = match (winners, draw_height) {
+        (Some(winners), Some(draw_height)) => format!(
+            "{winners} winner(s) will be drawn at random from all participants, \
+            with the hash of block {draw_height}\n\n"
+        ),
+        (Some(winners), None) => {
+            format!("{winners} winner(s) will be drawn at random from all participants\n\n")
+        }
+        _ => String::new(),
+    };
+
+    let requirements_text = requirements
+        .describe()
+        .map(|description| format!("{description}\n\n"))
+        .unwrap_or_default();
+
+    format!(
+        ">>> **A reactdrop of {amount} was started by {}!**\n\n\
+    React with the {reaction_type} emoji to participate\n\n{giveaway}{requirements_text}{}",
+        pool::mention(author),
+        reactdrop::countdown(finish_time),
+    )
+}
+
+/// Start a giveaway where users need to react to a message to participate
+///
+/// -------- :robot: **Starting a reactdrop** --------
+/// When initiating a reactdrop, find a suitable emoji in the first parameter. \
+/// It can be any Emoji, as long as the emoji is in the current server.
+///
+/// The amount is entered in the second parameter. This amount is taken from your balance \
+/// when the reactdrop starts and will be split among the participants of the reactdrop \
+/// when it ends. If nobody participates, you get it back.
+///
+/// Optionally, only a number of winners drawn at random from the participants share the amount, \
+/// with fewer participants than winners they all share it. \
+/// The draw is seeded with the hash of a block that is announced when the reactdrop starts \
+/// and mined after it ends, so everyone can verify the outcome. A giveaway can't be cancelled \
+/// or extended anymore once it ended.
+///
+/// To keep alt accounts out, you can require participants to have a role, an account or \
+/// server membership of a minimum age, and cap the number of participants.
+///
+/// A reactdrop can also be scheduled to start later. The amount is then taken from your \
+/// balance right away, and the reactdrop starts in this channel when the time has come. \
+/// `/scheduled` lists the scheduled reactdrops of this server and cancels yours.
+///
+/// Members that may spend from a pool can pay for a reactdrop from that pool. Whatever is not \
+/// paid out goes back to the pool.
+///
+/// -------- :robot: **Managing reactdrops** --------
+/// `/reactdrop list` shows the reactdrops that are running in this server, with their id. \
+/// With that id you can cancel your reactdrop, which gives the amount back to you, \
+/// or extend it to give people more time to join.
+#[instrument(skip(_ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(
+    slash_command,
+    category = "Tipping",
+    subcommands("start", "list", "cancel", "extend")
+)]
+pub async fn reactdrop(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Start a reactdrop: split an amount among everyone who reacts to it.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Tipping")]
+async fn start(
+    ctx: Context<'_>,
+    #[description = "The emoji users need to react with"] emoji: String,
+    #[min = 0.1]
+    #[description = "The amount you want to give away"]
+    amount: f64,
+    #[min = 1]
+    #[max = 2592000]
+    time: i64,
+    #[description = "The time in days, hours, minutes or seconds"] hms: Hms,
+    #[min = 1]
+    #[description = "Give the whole amount to this many randomly drawn participants"]
+    winners: Option<u32>,
+    #[description = "Only members with this role can participate"] required_role: Option<Role>,
+    #[min = 1]
+    #[description = "Only accounts that are at least this many days old can participate"]
+    min_account_age_days: Option<u32>,
+    #[min = 1]
+    #[description = "Only members that joined this server at least this many days ago can participate"]
+    min_membership_days: Option<u32>,
+    #[min = 1]
+    #[description = "At most this many participants, picked at random, get a share"]
+    max_participants: Option<u32>,
+    #[min = 1]
+    #[description = "Start the reactdrop later, after this many minutes (or the unit below)"]
+    delay: Option<i64>,
+    #[description = "The unit of the delay"] delay_unit: Option<Hms>,
+    #[description = "Pay from this pool instead of your own balance"] pool: Option<String>,
+) -> Result<(), Error> {
+    if user_blacklisted(ctx, ctx.author().id).await? {
+        return Ok(());
+    }
+
+    if ctx.guild_id().is_none() && (required_role.is_some() || min_membership_days.is_some()) {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("A role or membership requirement only works in a server"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let Some(time_in_seconds) = hms
+        .duration(time)
+        .filter(|duration| *duration >= MIN_DURATION)
+    else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("A reactdrop can last between 30 seconds and 30 days"),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let run_at = match scheduled_time(delay, delay_unit, ctx.guild_id(), chrono::Utc::now()) {
+        Ok(run_at) => run_at,
+        Err(message) => {
+            ctx.send(CreateReply::default().ephemeral(true).content(message))
+                .await?;
+
+            return Ok(());
+        }
+    };
+
+    let Some(payer) = paying_account(ctx, pool.as_deref()).await? else {
+        return Ok(());
+    };
+
+    let tip_amount = Amount::from_vrsc(amount)?;
+
+    if get_and_check_balance_of(&ctx, payer, tip_amount, Amount::ZERO)
+        .await?
+        .is_some()
+    {
+        debug!("emoji picked for reactdrop: {}", emoji);
+
+        if let Ok(reaction_type) = ReactionType::try_from(emoji) {
+            match &reaction_type {
+                ReactionType::Custom { id, .. } => {
+                    let emojis = ctx.guild_id().unwrap().emojis(&ctx.http()).await?;
+                    if !emojis.iter().any(|e| e.id == id.get()) {
+                        trace!("emoji not in guild");
+                        ctx.send(CreateReply::default().ephemeral(true).content(
+                            "This emoji is not found in this Discord server, so it can't be \
+                                    used. Please pick another one",
+                        ))
+                        .await?;
+
+                        return Ok(());
+                    } else {
+                        debug!("emoji in guild");
+                    }
+                }
+                ReactionType::Unicode(unicode) => {
+                    let emoji = emojis::get(unicode);
+
+                    if emoji.is_none() {
+                        ctx.send(CreateReply::default().ephemeral(true).content(
+                            "This is not a valid emoji. \
+                                    Please pick an emoji to start a Reactdrop",
+                        ))
+                        .await?;
+
+                        return Ok(());
+                    } else {
+                        trace!("valid unicode");
+                    }
+                }
+                ref s => {
+                    unreachable!("we find ourselves in a weird state: {:?}", s);
+                }
+            }
+
+            trace!("valid emoji");
+
+            let requirements = Requirements {
+                role: required_role.map(|role| role.id),
+                min_account_age_days,
+                min_membership_days,
+                max_participants,
+            };
+
+            if let Some(run_at) = run_at {
+                let mut tx = ctx.data().database.begin().await?;
+                let id = scheduler::schedule(
+                    &mut tx,
+                    payer,
+                    Some(ctx.author().id),
+                    ctx.guild_id().unwrap(),
+                    ctx.channel_id(),
+                    tip_amount,
+                    &ScheduledAction::Reactdrop {
+                        emoji: reaction_type.to_string(),
+                        duration_secs: time_in_seconds.num_seconds(),
+                        winners,
+                        requirements,
+                    },
+                    run_at,
+                )
+                .await?;
+                audit_spend(
+                    &mut tx,
+                    ctx,
+                    payer,
+                    format!("reactdrop of {tip_amount} (scheduled tip #{id})"),
+                )
+                .await?;
+                tx.commit().await?;
+
+                debug!("reactdrop scheduled at {run_at} (#{id})");
+
+                ctx.send(CreateReply::default().ephemeral(true).content(format!(
+                    "Your reactdrop of {tip_amount} will start <t:{}:R> (scheduled tip #{id}). \
+                    The amount is held until then, `/scheduled cancel` gives it back.",
+                    run_at.timestamp()
+                )))
+                .await?;
+
+                return Ok(());
+            }
+
+            let draw_height = match winners {
+                Some(_) => Some(reactdrop::draw_height(
+                    ctx.data().verus()?.get_blockchain_info()?.blocks,
+                    time_in_seconds.num_seconds(),
+                )),
+                None => None,
+            };
+
+            let now = chrono::Utc::now();
+            // sane values are guaranteed by the check on the duration
+            let finish_time = now.checked_add_signed(time_in_seconds).unwrap();
+            debug!("finish_time: {finish_time:?}");
+
+            // the amount is held from the moment the reactdrop starts, so the author can't spend it
+            // in the meantime. If posting the reactdrop fails, the transaction is rolled back.
+            let mut tx = ctx.data().database.begin().await?;
+            database::decrease_balance(
+                &mut tx,
+                &payer,
+                &tip_amount,
+                &Amount::ZERO,
+                &Address::from_str(VRSC_CURRENCY_ID)?,
+            )
+            .await?;
+
+            let reply_handle = ctx
+                .send(
+                    CreateReply::default()
+                        .content(announcement(
+                            payer,
+                            tip_amount,
+                            &reaction_type,
+                            winners,
+                            draw_height,
+                            &requirements,
+                            finish_time,
+                        ))
+                        // don't ping the required role
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await?;
+            let msg = reply_handle.into_message().await?;
+            msg.react(ctx.http(), reaction_type.clone()).await?;
+
+            // a reactdrop can be started for as long as a user wants it to last.
+            // Discord however limits the lifetime of a context to 15 minutes.
+            // We must account for this by extracting the necessary data from `Context`
+            // and store it for later use.
+            let channel_id = ctx.channel_id();
+            let message_id = msg.id;
+
+            database::insert_reactdrop(
+                &mut tx,
+                payer.get() as i64,
+                Some(ctx.author().id.get() as i64),
+                reaction_type.to_string(),
+                tip_amount.as_sat() as i64,
+                channel_id.into(),
+                message_id.into(),
+                finish_time,
+                &Address::from_str(VRSC_CURRENCY_ID)?,
+                ctx.guild_id().map(|guild_id| guild_id.get() as i64),
+                winners,
+                draw_height,
+                &requirements,
+            )
+            .await?;
+
+            audit_spend(
+                &mut tx,
+                ctx,
+                payer,
+                format!("reactdrop of {tip_amount} (message {message_id})"),
+            )
+            .await?;
+
+            tx.commit().await?;
+
+            ctx.data().reactdrop_wakeup.notify_one();
+        }
+    }
+
+    Ok(())
+}
+
+/// List the reactdrops that are running in this server
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Tipping")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut conn = ctx.data().database.acquire().await?;
+
+    let mut reactdrops = database::get_pending_reactdrops(&mut conn)
+        .await?
+        .into_iter()
+        .filter(|reactdrop| reactdrop.guild_id == ctx.guild_id())
+        .collect::<Vec<_>>();
+    reactdrops.sort_by_key(|reactdrop| reactdrop.finish_time);
+
+    if reactdrops.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("There are no reactdrops running in this server"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let lines = reactdrops
+        .iter()
+        .take(MAX_LISTED_REACTDROPS)
+        .map(|reactdrop| {
+            format!(
+                "`#{}` {} {} by {}, ends <t:{}:R> ([jump]({}))",
+                reactdrop.id,
+                reactdrop.tip_amount,
+                reactdrop.emoji,
+                pool::mention(reactdrop.author),
+                reactdrop.finish_time.timestamp(),
+                reactdrop
+                    .message_id
+                    .link(reactdrop.channel_id, reactdrop.guild_id),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut embed = CreateEmbed::new()
+        .title("Running reactdrops")
+        .description(lines);
+    if reactdrops.len() > MAX_LISTED_REACTDROPS {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "and {} more",
+            reactdrops.len() - MAX_LISTED_REACTDROPS
+        )));
+    }
+
+    ctx.send(CreateReply::default().ephemeral(true).embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Cancel a running reactdrop and get its amount back
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Tipping")]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "The id of the reactdrop, as shown by /reactdrop list"] id: i64,
+) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(reactdrop) = managed_reactdrop(ctx, &mut tx, id).await? else {
+        return Ok(());
+    };
+
+    if !database::cancel_reactdrop(&mut tx, id).await? {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("This reactdrop has ended and can't be cancelled anymore"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    reactdrop::refund(&mut tx, &reactdrop).await?;
+
+    if reactdrop.author != ctx.author().id {
+        audit(
+            &mut tx,
+            ctx,
+            "reactdrop cancel",
+            id.to_string(),
+            Some(ReactdropState::Pending.to_string()),
+            Some(ReactdropState::Cancelled.to_string()),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!("reactdrop {id} cancelled by {}", ctx.author().id);
+
+    if let Err(e) = reactdrop::close_message(
+        ctx.http(),
+        &reactdrop,
+        &format!("This reactdrop was cancelled by <@{}>", ctx.author().id),
+    )
+    .await
+    {
+        warn!("could not update the message of cancelled reactdrop {id}: {e:?}");
+    }
+
+    let content = if reactdrop.escrowed {
+        format!(
+            "Reactdrop #{id} was cancelled, {} was returned to {}",
+            reactdrop.tip_amount,
+            pool::mention(reactdrop.author)
+        )
+    } else {
+        format!("Reactdrop #{id} was cancelled")
+    };
+
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// Give people more time to join a running reactdrop
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Tipping")]
+async fn extend(
+    ctx: Context<'_>,
+    #[description = "The id of the reactdrop, as shown by /reactdrop list"] id: i64,
+    #[min = 1]
+    #[max = 2592000]
+    time: i64,
+    #[description = "The extra time in days, hours, minutes or seconds"] hms: Hms,
+) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(reactdrop) = managed_reactdrop(ctx, &mut tx, id).await? else {
+        return Ok(());
+    };
+
+    let Some(extra) = hms.duration(time) else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("A reactdrop can be extended by at most 30 days"),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let finish_time = reactdrop.finish_time + extra;
+    if finish_time - chrono::Utc::now() > MAX_DURATION {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("A reactdrop can end at most 30 days from now"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    if !database::set_reactdrop_finish_time(&mut tx, id, finish_time).await? {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("This reactdrop has ended and can't be extended anymore"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    tx.commit().await?;
+    ctx.data().reactdrop_wakeup.notify_one();
+
+    debug!("reactdrop {id} now ends at {finish_time}");
+
+    let reactdrop = Reactdrop {
+        finish_time,
+        ..reactdrop
+    };
+    if let Err(e) = reactdrop::update_countdown(ctx.http(), &reactdrop).await {
+        warn!("could not update the message of extended reactdrop {id}: {e:?}");
+    }
+
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "Reactdrop #{id} now ends <t:{}:R>",
+        finish_time.timestamp()
+    )))
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the pending reactdrop with `id` if the author of the command may manage it: only
+/// its author and admins can. Tells the user why not otherwise.
+async fn managed_reactdrop(
+    ctx: Context<'_>,
+    conn: &mut PgConnection,
+    id: i64,
+) -> Result<Option<Reactdrop>, Error> {
+    let reactdrop = database::get_pending_reactdrop(conn, id).await?;
+
+    let allowed = match &reactdrop {
+        Some(reactdrop) => {
+            reactdrop.author == ctx.author().id
+                || may_spend_from(ctx, reactdrop.author).await?
+                || is_support(ctx).await?
+        }
+        None => false,
+    };
+
+    if !allowed {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("You don't have a running reactdrop with id #{id}")),
+        )
+        .await?;
+
+        return Ok(None);
+    }
+
+    Ok(reactdrop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(Hms::Days.duration(2), Some(Duration::days(2)));
+        assert_eq!(Hms::Seconds.duration(45), Some(Duration::seconds(45)));
+        assert_eq!(Hms::Days.duration(31), None);
+        assert_eq!(Hms::Hours.duration(i64::MAX), None);
+        // fits in an i64 as seconds, but not in a `Duration`
+        assert_eq!(Hms::Minutes.duration(1_000_000_000_000_000), None);
+    }
+
+    #[test]
+    fn schedule_times() {
+        let now = chrono::Utc::now();
+        let guild_id = Some(GuildId::new(1));
+
+        assert_eq!(scheduled_time(None, None, None, now), Ok(None));
+        assert_eq!(
+            scheduled_time(Some(5), None, guild_id, now),
+            Ok(Some(now + Duration::minutes(5)))
+        );
+        assert_eq!(
+            scheduled_time(Some(2), Some(Hms::Hours), guild_id, now),
+            Ok(Some(now + Duration::hours(2)))
+        );
+        assert!(scheduled_time(Some(31), Some(Hms::Days), guild_id, now).is_err());
+        assert!(scheduled_time(Some(5), None, None, now).is_err());
+    }
+}
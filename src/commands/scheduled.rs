@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+use poise::{
+    CreateReply,
+    serenity_prelude::{CreateEmbed, CreateEmbedFooter},
+};
+use tracing::*;
+use uuid::Uuid;
+use vrsc::Address;
+
+use crate::{
+    Context, Error, VRSC_CURRENCY_ID,
+    commands::{
+        admin::{audit, is_support},
+        pool::may_spend_from,
+    },
+    database, pool,
+    scheduler::{ScheduledAction, ScheduledTipStatus},
+};
+
+/// The most scheduled tips `/scheduled list` shows.
+const MAX_LISTED_SCHEDULED_TIPS: usize = 20;
+
+/// See or cancel tips and reactdrops that were scheduled for later
+///
+/// -------- :robot: **Scheduled tips** --------
+/// `/scheduled list` shows the role tips and reactdrops in this server that are scheduled for \
+/// later, with their id. With that id you can cancel your scheduled tip before it is due, \
+/// which gives the held amount back to you.
+#[instrument(skip(_ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "Tipping",
+    subcommands("list", "cancel")
+)]
+pub async fn scheduled(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// List the tips and reactdrops that are scheduled in this server
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Tipping")]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let scheduled_tips = {
+        let mut conn = ctx.data().database.acquire().await?;
+        database::get_pending_scheduled_tips(&mut conn, ctx.guild_id().unwrap()).await?
+    };
+
+    if scheduled_tips.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("There are no tips scheduled in this server"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let lines = scheduled_tips
+        .iter()
+        .take(MAX_LISTED_SCHEDULED_TIPS)
+        .map(|scheduled_tip| {
+            let action = match &scheduled_tip.action {
+                ScheduledAction::Reactdrop { emoji, .. } => format!("{emoji} reactdrop"),
+                ScheduledAction::RoleTip { role_id, .. } => format!("tip to <@&{role_id}>"),
+            };
+
+            format!(
+                "`#{}` {} {action} by {} in <#{}>, <t:{}:R>",
+                scheduled_tip.id,
+                scheduled_tip.amount,
+                pool::mention(scheduled_tip.author),
+                scheduled_tip.channel_id,
+                scheduled_tip.run_at.timestamp(),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut embed = CreateEmbed::new()
+        .title("Scheduled tips")
+        .description(lines);
+    if scheduled_tips.len() > MAX_LISTED_SCHEDULED_TIPS {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "and {} more",
+            scheduled_tips.len() - MAX_LISTED_SCHEDULED_TIPS
+        )));
+    }
+
+    ctx.send(CreateReply::default().ephemeral(true).embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Cancel a scheduled tip or reactdrop and get its amount back
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Tipping")]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "The id of the scheduled tip, as shown by /scheduled list"] id: i64,
+) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    let scheduled_tip = database::get_pending_scheduled_tip(&mut tx, id).await?;
+
+    // only its author, spenders of the pool it is paid from and admins can cancel it.
+    let allowed = match &scheduled_tip {
+        Some(scheduled_tip) => {
+            scheduled_tip.author == ctx.author().id
+                || may_spend_from(ctx, scheduled_tip.author).await?
+                || is_support(ctx).await?
+        }
+        None => false,
+    };
+    let Some(scheduled_tip) = scheduled_tip.filter(|_| allowed) else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("You don't have a scheduled tip with id #{id}")),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    // the scheduler may have picked it up in the meantime
+    if !database::update_scheduled_tip(
+        &mut tx,
+        id,
+        ScheduledTipStatus::Pending,
+        ScheduledTipStatus::Cancelled,
+        None,
+    )
+    .await?
+    {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("This tip was just sent or cancelled and can't be cancelled anymore"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    database::increase_balance(
+        &mut tx,
+        &scheduled_tip.author,
+        scheduled_tip.amount,
+        &Address::from_str(VRSC_CURRENCY_ID)?,
+    )
+    .await?;
+
+    if scheduled_tip.author != ctx.author().id {
+        audit(
+            &mut tx,
+            ctx,
+            "scheduled cancel",
+            id.to_string(),
+            Some(ScheduledTipStatus::Pending.to_string()),
+            Some(ScheduledTipStatus::Cancelled.to_string()),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!("scheduled tip {id} cancelled by {}", ctx.author().id);
+
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "Scheduled tip #{id} was cancelled, {} was returned to {}",
+        scheduled_tip.amount,
+        pool::mention(scheduled_tip.author)
+    )))
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+use chrono::Duration;
+use poise::{
+    ChoiceParameter, CreateReply,
+    serenity_prelude::{Colour, CreateEmbed, User, UserId},
+};
+use sqlx::types::chrono::{DateTime, Utc};
+use tracing::instrument;
+use uuid::Uuid;
+use vrsc::{Address, Amount};
+
+use crate::{Context, Error, database, pool};
+
+const LEADERBOARD_SIZE: i64 = 10;
+
+#[derive(Debug, Clone, Copy, ChoiceParameter)]
+pub enum TimeWindow {
+    #[name = "Today"]
+    Day,
+    #[name = "This week"]
+    Week,
+    #[name = "This month"]
+    Month,
+    #[name = "All time"]
+    All,
+}
+
+impl TimeWindow {
+    /// The start of the window, or `None` if the window covers all time.
+    ///
+    /// Windows are rolling: "this week" means the last 7 days.
+    pub fn since(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+
+        match self {
+            Self::Day => Some(now - Duration::days(1)),
+            Self::Week => Some(now - Duration::weeks(1)),
+            Self::Month => Some(now - Duration::days(30)),
+            Self::All => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LeaderboardEntry {
+    pub user_id: UserId,
+    pub total: Amount,
+    /// The number of tips sent or received.
+    pub count: i64,
+}
+
+#[derive(Debug)]
+pub struct TipStats {
+    pub sent: Amount,
+    /// The number of tips sent, a group tip counts as 1.
+    pub sent_count: i64,
+    pub largest_sent: Amount,
+    pub received: Amount,
+    pub received_count: i64,
+    pub largest_received: Amount,
+    pub reactdrops_joined: i64,
+}
+
+/// Show the top tippers and receivers in this server
+///
+/// -------- :robot: **Leaderboard** --------
+/// Shows the 10 users that tipped the most and the 10 users that received the most tips \
+/// in this server. Pick a time window to only count recent tips.
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, guild_only, category = "Tipping")]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "The period to count tips in, all time by default"] window: Option<TimeWindow>,
+    #[description = "The currency to count tips in, VRSC by default"] currency: Option<String>,
+) -> Result<(), Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(());
+    };
+
+    let window = window.unwrap_or(TimeWindow::All);
+    let Ok(currency_id) = ctx
+        .data()
+        .to_currency_id(currency.as_deref().unwrap_or("VRSC"))
+    else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("Currency not found"),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let mut conn = ctx.data().database.acquire().await?;
+    let tippers = database::get_top_tippers(
+        &mut conn,
+        guild_id,
+        window.since(),
+        &currency_id,
+        LEADERBOARD_SIZE,
+    )
+    .await?;
+    let receivers = database::get_top_receivers(
+        &mut conn,
+        guild_id,
+        window.since(),
+        &currency_id,
+        LEADERBOARD_SIZE,
+    )
+    .await?;
+
+    let currency_name = currency_name(ctx, &currency_id);
+
+    ctx.send(
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(format!("Tip leaderboard ({})", window.name()))
+                .field(
+                    "Top tippers",
+                    leaderboard_str(&tippers, &currency_name),
+                    false,
+                )
+                .field(
+                    "Top receivers",
+                    leaderboard_str(&receivers, &currency_name),
+                    false,
+                )
+                .color(Colour::GOLD),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Show tipping statistics of yourself or another user
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Tipping")]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "The user to show statistics of, yourself by default"] user: Option<User>,
+    #[description = "The currency to show statistics in, VRSC by default"] currency: Option<String>,
+) -> Result<(), Error> {
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let Ok(currency_id) = ctx
+        .data()
+        .to_currency_id(currency.as_deref().unwrap_or("VRSC"))
+    else {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("Currency not found"),
+        )
+        .await?;
+
+        return Ok(());
+    };
+
+    let mut conn = ctx.data().database.acquire().await?;
+    let stats = database::get_tip_stats(&mut conn, user.id, &currency_id).await?;
+    let currency_name = currency_name(ctx, &currency_id);
+    let fmt = |amount: Amount| {
+        format!(
+            "{} {currency_name}",
+            amount.to_string_in(vrsc::Denomination::Verus)
+        )
+    };
+
+    ctx.send(
+        CreateReply::default().ephemeral(true).embed(
+            CreateEmbed::new()
+                .title(format!("Tipping statistics of {}", user.name))
+                .field(
+                    "Sent",
+                    format!("{} in {} tip(s)", fmt(stats.sent), stats.sent_count),
+                    true,
+                )
+                .field("Largest tip sent", fmt(stats.largest_sent), true)
+                .field("\u{200b}", "\u{200b}", true)
+                .field(
+                    "Received",
+                    format!("{} in {} tip(s)", fmt(stats.received), stats.received_count),
+                    true,
+                )
+                .field("Largest tip received", fmt(stats.largest_received), true)
+                .field(
+                    "Reactdrops joined",
+                    stats.reactdrops_joined.to_string(),
+                    true,
+                )
+                .color(Colour::BLURPLE),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn currency_name(ctx: Context<'_>, currency_id: &Address) -> String {
+    ctx.data()
+        .to_currency_name(currency_id)
+        .unwrap_or_else(|_| currency_id.to_string())
+}
+
+fn leaderboard_str(entries: &[LeaderboardEntry], currency_name: &str) -> String {
+    if entries.is_empty() {
+        return "_nobody yet_".to_string();
+    }
+
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            format!(
+                "{}. {} {} {currency_name} ({} tips)",
+                i + 1,
+                pool::mention(entry.user_id),
+                entry.total.to_string_in(vrsc::Denomination::Verus),
+                entry.count
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
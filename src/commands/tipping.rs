@@ -1,21 +1,34 @@
 use std::str::FromStr;
 
-use ::chrono::Duration;
 use poise::{
     CreateReply,
-    serenity_prelude::{self, CacheHttp, ChannelId, CreateMessage, ReactionType, RoleId, UserId},
+    serenity_prelude::{
+        self, ChannelId, Colour, CreateEmbed, CreateEmbedFooter, CreateMessage, GuildId,
+        OnlineStatus, RoleId, User, UserId,
+    },
 };
 
-use sqlx::{Postgres, Transaction, types::chrono};
+use futures::future::join_all;
+use rand::{SeedableRng, seq::SliceRandom};
+use rand_chacha::ChaCha20Rng;
+use sqlx::{PgConnection, types::chrono};
 use tracing::*;
 use uuid::Uuid;
 use vrsc::{Address, Amount};
 
 use crate::{
     Context, Error, VRSC_CURRENCY_ID,
-    commands::{misc::Notification, user_blacklisted},
+    commands::{
+        misc::{Notification, NotificationEvent},
+        pool::{audit_spend, paying_account},
+        reactdrop::{Hms, scheduled_time},
+        user_blacklisted,
+    },
     database,
-    wallet::get_and_check_balance,
+    notifier::{self, Recipient},
+    pool,
+    scheduler::{self, ScheduledAction},
+    wallet::{get_and_check_balance, get_and_check_balance_of},
 };
 
 /// Tip a user or a role
@@ -27,13 +40,27 @@ use crate::{
 /// Tip a role by entering and selecting the role name. The role name can be any role, \
 /// even the @everyone role.
 /// The amount entered in the second parameter will be split evenly among the members of the role.
+/// Optionally only tip members that are online or recently chatted, and cap the number of \
+/// members that get a tip; the lucky ones are then picked at random.
+/// A role tip can also be scheduled to be sent later; the amount is held until then. \
+/// `/scheduled` lists the scheduled tips of this server and cancels yours.
+/// Members that may spend from a pool can pay a user or role tip from that pool.
+///
+/// -------- :robot: **Tipping multiple users** --------
+/// Tip several users at once by mentioning them in the first parameter, e.g. `@alice @bob`.
+/// The amount is either given to each user, or split evenly among them.
 #[instrument(skip(_ctx), fields(request_id = %Uuid::new_v4() ))]
-#[poise::command(slash_command, category = "Tipping", subcommands("role", "user"))]
+#[poise::command(
+    slash_command,
+    category = "Tipping",
+    subcommands("role", "user", "users")
+)]
 pub async fn tip(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
 /// Tip a role by entering and selecting the role name.
+#[allow(clippy::too_many_arguments)]
 #[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
 #[poise::command(slash_command, category = "Tipping")]
 async fn role(
@@ -42,6 +69,22 @@ async fn role(
     #[description = "The amount you want to tip"]
     #[min = 0.5]
     tip_amount: f64,
+    #[description = "An optional message to go with your tip"]
+    #[max_length = 200]
+    note: Option<String>,
+    #[description = "Only tip members that are currently online"] online_only: Option<bool>,
+    #[description = "Only tip members that posted a message within this many minutes"]
+    #[min = 1]
+    #[max = 60]
+    active_within_minutes: Option<u64>,
+    #[description = "Tip at most this many members, picked at random"]
+    #[min = 1]
+    max_recipients: Option<usize>,
+    #[min = 1]
+    #[description = "Send the tip later, after this many minutes (or the unit below)"]
+    delay: Option<i64>,
+    #[description = "The unit of the delay"] delay_unit: Option<Hms>,
+    #[description = "Pay from this pool instead of your own balance"] pool: Option<String>,
 ) -> Result<(), Error> {
     let mut tx = ctx.data().database.begin().await?;
 
@@ -49,19 +92,79 @@ async fn role(
         return Ok(());
     }
 
+    let Some(payer) = paying_account(ctx, pool.as_deref()).await? else {
+        return Ok(());
+    };
+
+    let run_at = match scheduled_time(delay, delay_unit, ctx.guild_id(), chrono::Utc::now()) {
+        // who is online or active can only be known when the tip is sent
+        Ok(Some(_)) if online_only.is_some() || active_within_minutes.is_some() => {
+            Err("A scheduled tip can't be limited to online or active members")
+        }
+        run_at => run_at,
+    };
+    let run_at = match run_at {
+        Ok(run_at) => run_at,
+        Err(message) => {
+            ctx.send(CreateReply::default().ephemeral(true).content(message))
+                .await?;
+
+            return Ok(());
+        }
+    };
+
     debug!("role: {:?}", role.id);
     let tip_amount = Amount::from_vrsc(tip_amount)?;
 
-    if get_and_check_balance(&ctx, tip_amount, Amount::ZERO)
+    if get_and_check_balance_of(&ctx, payer, tip_amount, Amount::ZERO)
         .await?
         .is_some()
     {
         trace!("tipper has enough balance");
+
+        if let Some(run_at) = run_at {
+            let id = scheduler::schedule(
+                &mut tx,
+                payer,
+                Some(ctx.author().id),
+                ctx.guild_id().unwrap(),
+                ctx.channel_id(),
+                tip_amount,
+                &ScheduledAction::RoleTip {
+                    role_id: role.id,
+                    note,
+                    max_recipients,
+                },
+                run_at,
+            )
+            .await?;
+            audit_spend(
+                &mut tx,
+                ctx,
+                payer,
+                format!("{tip_amount} to role {} (scheduled tip #{id})", role.id),
+            )
+            .await?;
+            tx.commit().await?;
+
+            debug!("role tip scheduled at {run_at} (#{id})");
+
+            ctx.send(CreateReply::default().ephemeral(true).content(format!(
+                "Your tip of {tip_amount} to {} will be sent <t:{}:R> (scheduled tip #{id}). \
+                The amount is held until then, `/scheduled cancel` gives it back.",
+                role.name,
+                run_at.timestamp()
+            )))
+            .await?;
+
+            return Ok(());
+        }
         let guild_id = ctx.guild_id();
 
-        if let Some(members) = ctx.guild().map(|guild| guild.members.clone()) {
-            let guild_members = members.values();
-            let role_members = guild_members
+        let role_members = ctx.guild().map(|guild| {
+            guild
+                .members
+                .values()
                 .filter(
                     // @everyone role_id (same as guild_id) does never get tips
                     |m| {
@@ -69,17 +172,64 @@ async fn role(
                             || role.id == RoleId::new(guild_id.unwrap().get())
                     },
                 )
-                .map(|m| m.user.id)
-                .collect::<Vec<_>>();
+                .filter(|m| {
+                    !online_only.unwrap_or(false)
+                        || guild.presences.get(&m.user.id).is_some_and(|presence| {
+                            !matches!(
+                                presence.status,
+                                OnlineStatus::Offline | OnlineStatus::Invisible
+                            )
+                        })
+                })
+                .map(|m| m.user.clone())
+                .collect::<Vec<_>>()
+        });
+
+        if let Some(mut role_members) = role_members {
+            if let Some(minutes) = active_within_minutes {
+                let active = ctx.data().activity.active_in_guild(
+                    guild_id.unwrap(),
+                    std::time::Duration::from_secs(minutes * 60),
+                );
+                role_members.retain(|user| active.contains(&user.id));
+            }
+
+            let mut recipients =
+                eligible_recipients(&mut tx, payer, Some(ctx.author().id), &role_members).await?;
+
+            if let Some(max_recipients) = max_recipients {
+                recipients.sample(max_recipients);
+            }
+
+            if recipients.users.is_empty() {
+                ctx.send(
+                    CreateReply::default()
+                        .ephemeral(true)
+                        .content("No members of this role match your filters, nobody was tipped"),
+                )
+                .await?;
+
+                return Ok(());
+            }
 
             tip_multiple_users(
                 &mut tx,
-                ctx.author().id,
-                ctx.http(),
+                payer,
                 &ctx.channel_id(),
-                role_members,
+                ctx.guild_id(),
+                recipients,
                 &tip_amount,
                 "role",
+                note.as_deref(),
+                Funding::Balance,
+            )
+            .await?;
+
+            audit_spend(
+                &mut tx,
+                ctx,
+                payer,
+                format!("{tip_amount} to role {}", role.id),
             )
             .await?;
 
@@ -103,15 +253,23 @@ async fn user(
     ctx: Context<'_>,
     #[description = "Enter and select the user you want to tip"] user: serenity_prelude::User,
     #[description = "The amount you want to tip"] tip_amount: f64,
+    #[description = "An optional message to go with your tip"]
+    #[max_length = 200]
+    note: Option<String>,
+    #[description = "Pay from this pool instead of your own balance"] pool: Option<String>,
 ) -> Result<(), Error> {
     if user_blacklisted(ctx, ctx.author().id).await? {
         return Ok(());
     }
 
+    let Some(payer) = paying_account(ctx, pool.as_deref()).await? else {
+        return Ok(());
+    };
+
     let tip_amount = Amount::from_vrsc(tip_amount)?;
 
     debug!(
-        "user {} ({}) wants to tip {} with {tip_amount}",
+        "user {} ({}) wants to tip {} with {tip_amount} from {payer}",
         ctx.author().name,
         ctx.author().id,
         user.id
@@ -120,7 +278,7 @@ async fn user(
     // check if the tipper has enough balance
     // update both balances in 1 go
 
-    if get_and_check_balance(&ctx, tip_amount, Amount::ZERO)
+    if get_and_check_balance_of(&ctx, payer, tip_amount, Amount::ZERO)
         .await?
         .is_some()
     {
@@ -129,9 +287,8 @@ async fn user(
         let mut tx = ctx.data().database.begin().await?;
         database::process_a_tip(
             &mut tx,
-            ctx.author().id,
-            &[user.id],
-            tip_amount,
+            payer,
+            &[(user.id, tip_amount)],
             &Address::from_str(VRSC_CURRENCY_ID)?,
         )
         .await?;
@@ -141,289 +298,685 @@ async fn user(
         database::store_tip_transactions(
             &mut tx,
             &tip_event_id,
-            &vec![user.id],
+            &[(user.id, tip_amount)],
             "direct",
-            tip_amount,
-            ctx.author().id,
+            payer,
             &Address::from_str(VRSC_CURRENCY_ID)?,
+            ctx.guild_id(),
+            note.as_deref(),
+        )
+        .await?;
+
+        let receipt = tip_receipt_embed(
+            payer,
+            &format!("<@{}>", user.id),
+            tip_amount,
+            note.as_deref(),
+            &tip_event_id,
+        );
+
+        let notification = database::get_notification_settings(&mut tx, user.id)
+            .await?
+            .for_event(NotificationEvent::DirectTip);
+
+        if notification == Notification::DMOnly {
+            notifier::queue(
+                &mut tx,
+                Recipient::User(user.id),
+                &CreateMessage::new()
+                    .content(format!(
+                        "You just got tipped {tip_amount} from {}!",
+                        pool::mention(payer),
+                    ))
+                    .embed(receipt.clone()),
+            )
+            .await?;
+        }
+
+        audit_spend(
+            &mut tx,
+            ctx,
+            payer,
+            format!("{tip_amount} to user {} (tip {tip_event_id})", user.id),
         )
         .await?;
+
         tx.commit().await?;
 
-        let mut conn = ctx.data().database.acquire().await?;
-        match database::get_loudness_setting(&mut conn, user.id).await? {
-            Some(notification) => {
-                match notification {
-                    Notification::All | Notification::ChannelOnly => {
-                        // send a message in the same channel:
-                        ctx.send(CreateReply::default().ephemeral(false).content(format!(
-                            "<@{}> just tipped <@{}> {tip_amount}!",
-                            &ctx.author().id,
-                            user.id
-                        )))
-                        .await?;
-                    }
-                    Notification::DMOnly => {
-                        // send a non-pinging message in the channel:
-                        ctx.send(CreateReply::default().ephemeral(false).content(format!(
-                            "<@{}> just tipped `{}` {tip_amount}!",
-                            &ctx.author().id,
-                            user.tag()
-                        )))
-                        .await?;
-                        // send a notification in dm:
-                        user.dm(
-                            &ctx.http(),
-                            CreateMessage::new().content(format!(
-                                "You just got tipped {tip_amount} from <@{}>!",
+        // mentions in an embed never ping, so the tippee only gets pinged by a mention
+        // in the message content.
+        let mut reply = CreateReply::default().ephemeral(false).embed(receipt);
+        if notification.wants_mention() {
+            reply = reply.content(format!("<@{}>", user.id));
+        }
+        ctx.send(reply).await?;
+
+        return Ok(());
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum SplitMode {
+    #[name = "split"]
+    Split,
+    #[name = "each"]
+    Each,
+}
+
+/// Tip several users by mentioning them.
+#[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
+#[poise::command(slash_command, category = "Tipping")]
+async fn users(
+    ctx: Context<'_>,
+    #[description = "Mention the users you want to tip, e.g. @alice @bob"] users: String,
+    #[description = "The amount you want to tip"] tip_amount: f64,
+    #[description = "Split the amount among the users (default) or give each user the amount"]
+    mode: Option<SplitMode>,
+    #[description = "An optional message to go with your tip"]
+    #[max_length = 200]
+    note: Option<String>,
+) -> Result<(), Error> {
+    if user_blacklisted(ctx, ctx.author().id).await? {
+        return Ok(());
+    }
+
+    let (mentioned, unknown) = find_users(ctx, parse_user_mentions(&users)).await;
+
+    if mentioned.is_empty() && unknown == 0 {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("Please mention at least one user to tip, e.g. `@alice @bob`"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().database.begin().await?;
+    let Recipients { users, excluded } =
+        eligible_recipients(&mut tx, ctx.author().id, None, &mentioned).await?;
+    let excluded = excluded + unknown;
+    debug!("users in tip users: {:?}, {excluded} excluded", users);
+
+    if users.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("None of the mentioned users can receive tips"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let tip_amount = Amount::from_vrsc(tip_amount)?;
+    let tip_event_id = Uuid::new_v4();
+
+    let shares = match mode.unwrap_or(SplitMode::Split) {
+        SplitMode::Split => split_amount(tip_amount, &users, &tip_event_id),
+        SplitMode::Each => users.iter().map(|user_id| (*user_id, tip_amount)).collect(),
+    };
+    let total_amount = Amount::from_sat(shares.iter().map(|(_, amount)| amount.as_sat()).sum());
+
+    if shares.iter().any(|(_, amount)| *amount == Amount::ZERO) {
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content("The amount is too small to split among that many users"),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    if get_and_check_balance(&ctx, total_amount, Amount::ZERO)
+        .await?
+        .is_some()
+    {
+        trace!("tipper has enough balance");
+
+        let currency_id = Address::from_str(VRSC_CURRENCY_ID)?;
+
+        database::process_a_tip(&mut tx, ctx.author().id, &shares, &currency_id).await?;
+
+        database::store_tip_transactions(
+            &mut tx,
+            &tip_event_id,
+            &shares,
+            "users",
+            ctx.author().id,
+            &currency_id,
+            ctx.guild_id(),
+            note.as_deref(),
+        )
+        .await?;
+
+        let mentions = users
+            .iter()
+            .map(|user_id| format!("<@{user_id}>"))
+            .collect::<Vec<_>>();
+
+        let mut receipt = tip_receipt_embed(
+            ctx.author().id,
+            &mentions.join(", "),
+            total_amount,
+            note.as_deref(),
+            &tip_event_id,
+        );
+
+        if excluded > 0 {
+            receipt = receipt.field(
+                "Excluded",
+                format!("{excluded} (bots, the tipper, suspended or unknown users)"),
+                false,
+            );
+        }
+
+        let mut pings = vec![];
+        for ((user_id, amount), mention) in shares.iter().zip(mentions) {
+            match database::get_notification_settings(&mut tx, *user_id)
+                .await?
+                .for_event(NotificationEvent::DirectTip)
+            {
+                Notification::All | Notification::ChannelOnly => pings.push(mention),
+                Notification::DMOnly => {
+                    notifier::queue(
+                        &mut tx,
+                        Recipient::User(*user_id),
+                        &CreateMessage::new()
+                            .content(format!(
+                                "You just got tipped {amount} from <@{}>!",
                                 &ctx.author().id,
-                            )),
-                        )
-                        .await?;
-                    }
-                    Notification::Off => {
-                        // send a non-pinging message in the channel:
-                        ctx.send(CreateReply::default().ephemeral(false).content(format!(
-                            "<@{}> just tipped `{}` {tip_amount}!",
-                            &ctx.author().id,
-                            user.tag()
-                        )))
-                        .await?;
-                    }
+                            ))
+                            .embed(receipt.clone()),
+                    )
+                    .await?;
                 }
-            }
-            None => {
-                trace!("User has not set notification settings, defaulting to Channel");
-
-                ctx.send(CreateReply::default().ephemeral(false).content(format!(
-                    "<@{}> just tipped <@{}> {tip_amount}!",
-                    &ctx.author().id,
-                    user.id
-                )))
-                .await?;
+                Notification::Off => {}
             }
         }
 
-        return Ok(());
+        tx.commit().await?;
+
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(false)
+                .content(pings.join(" "))
+                .embed(receipt),
+        )
+        .await?;
     }
 
     Ok(())
 }
 
-#[derive(Debug, poise::ChoiceParameter)]
-pub enum Hms {
-    Hours,
-    Minutes,
+/// Parses all user mentions (`<@id>` or `<@!id>`) in `input`, in order of appearance and
+/// without duplicates. Anything else, including role and channel mentions, is ignored.
+fn parse_user_mentions(input: &str) -> Vec<UserId> {
+    let mut users = vec![];
+
+    for part in input.split('<').skip(1) {
+        let Some((mention, _)) = part.split_once('>') else {
+            continue;
+        };
+        let Some(id) = mention.strip_prefix('@') else {
+            continue;
+        };
+        let id = id.strip_prefix('!').unwrap_or(id);
+
+        let Some(user_id) = id.parse::<u64>().ok().filter(|id| *id != 0) else {
+            continue;
+        };
+
+        if !users.contains(&UserId::new(user_id)) {
+            users.push(UserId::new(user_id));
+        }
+    }
+
+    users
 }
 
-/// Start a giveaway where users need to react to a message to participate
-///
-/// -------- :robot: **Reactdrop** --------
-/// When initiating a reactdrop, find a suitable emoji in the first parameter. \
-/// It can be any Emoji, as long as the emoji is in the current server.
+/// Split an amount among the users that recently chatted in this channel
 ///
-/// The amount is entered in the second parameter. This amount will be split \
-/// among the participants of the reactdrop when it ends.
+/// -------- :robot: **Rain** --------
+/// Everyone who posted a message in this channel during the last minutes (10 by default) \
+/// gets an equal share of the amount. Bots and you yourself are not included.
 #[instrument(skip(ctx), fields(request_id = %Uuid::new_v4() ))]
-#[poise::command(slash_command, category = "Tipping")]
-pub async fn reactdrop(
+#[poise::command(slash_command, guild_only, category = "Tipping")]
+pub async fn rain(
     ctx: Context<'_>,
-    #[description = "The emoji users need to react with"] emoji: String,
+    #[description = "The amount you want to split among the active chatters"]
     #[min = 0.1]
-    #[description = "The amount you want to give away"]
     amount: f64,
-    #[min = 1] time: i64,
-    #[description = "The time in hours, minutes or seconds"] hms: Hms,
+    #[description = "How far back to look for chatters, in minutes (default 10)"]
+    #[min = 1]
+    #[max = 60]
+    minutes: Option<u64>,
+    #[description = "An optional message to go with your tip"]
+    #[max_length = 200]
+    note: Option<String>,
 ) -> Result<(), Error> {
     if user_blacklisted(ctx, ctx.author().id).await? {
         return Ok(());
     }
 
     let tip_amount = Amount::from_vrsc(amount)?;
+    let window = std::time::Duration::from_secs(minutes.unwrap_or(10) * 60);
+
+    let (chatters, unknown) = find_users(
+        ctx,
+        ctx.data()
+            .activity
+            .active_in_channel(ctx.channel_id(), window),
+    )
+    .await;
+
+    let mut tx = ctx.data().database.begin().await?;
+    let mut recipients = eligible_recipients(&mut tx, ctx.author().id, None, &chatters).await?;
+    recipients.excluded += unknown;
+    debug!("recipients of rain: {:?}", recipients);
+
+    if recipients.users.is_empty() {
+        ctx.send(
+            CreateReply::default().ephemeral(true).content(
+                "Nobody else has been chatting here lately, so there is no one to rain on",
+            ),
+        )
+        .await?;
+
+        return Ok(());
+    }
 
     if get_and_check_balance(&ctx, tip_amount, Amount::ZERO)
         .await?
         .is_some()
     {
-        debug!("emoji picked for reactdrop: {}", emoji);
-
-        if let Ok(reaction_type) = ReactionType::try_from(emoji) {
-            match &reaction_type {
-                ReactionType::Custom { id, .. } => {
-                    let emojis = ctx.guild_id().unwrap().emojis(&ctx.http()).await?;
-                    if !emojis.iter().any(|e| e.id == id.get()) {
-                        trace!("emoji not in guild");
-                        ctx.send(CreateReply::default().ephemeral(true).content(
-                            "This emoji is not found in this Discord server, so it can't be \
-                                    used. Please pick another one",
-                        ))
-                        .await?;
-
-                        return Ok(());
-                    } else {
-                        debug!("emoji in guild");
-                    }
-                }
-                ReactionType::Unicode(unicode) => {
-                    let emoji = emojis::get(unicode);
-
-                    if emoji.is_none() {
-                        ctx.send(CreateReply::default().ephemeral(true).content(
-                            "This is not a valid emoji. \
-                                    Please pick an emoji to start a Reactdrop",
-                        ))
-                        .await?;
-
-                        return Ok(());
-                    } else {
-                        trace!("valid unicode");
-                    }
-                }
-                ref s => {
-                    unreachable!("we find ourselves in a weird state: {:?}", s);
-                }
-            }
+        trace!("tipper has enough balance");
+        let number_of_users = recipients.users.len();
 
-            trace!("valid emoji");
-
-            let time_in_seconds: Duration = match hms {
-                Hms::Hours => Duration::seconds(time * 60 * 60),
-                Hms::Minutes => Duration::seconds(time * 60),
-            };
-
-            let now = chrono::Utc::now();
-            // sane values are guaranteed by command argument limits
-            let finish_time = now.checked_add_signed(time_in_seconds).unwrap();
-            debug!("finish_time: {finish_time:?}");
-
-            let reply_handle = ctx
-                .say(format!(
-                    ">>> **A reactdrop of {tip_amount} was started!**\n\n\
-    React with the {} emoji to participate\n\n
-    Time remaining: {} hour(s) and {} minute(s)",
-                    reaction_type.clone(),
-                    time_in_seconds.num_seconds() / (60 * 60),
-                    (time_in_seconds.num_seconds() / 60) % 60
-                ))
-                .await?;
-            let msg = reply_handle.into_message().await?;
-            msg.react(ctx.http(), reaction_type.clone()).await?;
-
-            // a reactdrop can be started for as long as a user wants it to last.
-            // Discord however limits the lifetime of a context to 15 minutes.
-            // We must account for this by extracting the necessary data from `Context`
-            // and store it for later use.
-            let channel_id = ctx.channel_id();
-            let message_id = msg.id;
-
-            let mut conn = ctx.data().database.acquire().await?;
-
-            database::insert_reactdrop(
-                &mut conn,
-                ctx.author().id.into(),
-                reaction_type.to_string(),
-                Amount::from_vrsc(amount).unwrap().as_sat() as i64,
-                channel_id.into(),
-                message_id.into(),
-                finish_time,
-                &Address::from_str(VRSC_CURRENCY_ID)?,
-            )
-            .await?;
-        }
+        tip_multiple_users(
+            &mut tx,
+            ctx.author().id,
+            &ctx.channel_id(),
+            ctx.guild_id(),
+            recipients,
+            &tip_amount,
+            "rain",
+            note.as_deref(),
+            Funding::Balance,
+        )
+        .await?;
+        tx.commit().await?;
+
+        ctx.send(
+            CreateReply::default()
+                .ephemeral(true)
+                .content(format!("It rained on {number_of_users} users!")),
+        )
+        .await?;
     }
 
     Ok(())
 }
 
-// Divides the amount over the `users` vec, increases the balance for all `users`
-// and stores the tip transaction
-// This function gets called in `tip role` and `reactdrop`
+/// Where the money of a group tip comes from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Funding {
+    /// Taken from the tipper's balance when the tip is sent.
+    Balance,
+    /// Already taken from the tipper when the reactdrop started or the tip was scheduled.
+    Escrow,
+}
+
+/// The users that receive a share of a group tip, and how many candidates were left out.
+#[derive(Debug, Clone)]
+pub struct Recipients {
+    pub users: Vec<UserId>,
+    pub excluded: usize,
+}
+
+impl Recipients {
+    /// Keeps at most `max` of the recipients, picked at random. Users that are not picked
+    /// don't count as excluded.
+    pub fn sample(&mut self, max: usize) {
+        if self.users.len() > max {
+            self.users.shuffle(&mut rand::rng());
+            self.users.truncate(max);
+        }
+    }
+}
+
+/// The eligibility rules for every distribution over a group of users (role tips, rain,
+/// reactdrops): bots, the tipper themself and blacklisted users never get a share, and nobody
+/// gets more than one share. When the tipper is a pool, `spender` is the member that spends
+/// from it, who doesn't get a share either.
+pub async fn eligible_recipients(
+    conn: &mut PgConnection,
+    tipper: UserId,
+    spender: Option<UserId>,
+    candidates: &[User],
+) -> Result<Recipients, Error> {
+    let mut users = vec![];
+    for user in candidates {
+        if !user.bot && user.id != tipper && Some(user.id) != spender && !users.contains(&user.id) {
+            users.push(user.id);
+        }
+    }
+
+    let blacklisted = database::get_blacklisted_users(conn, &users).await?;
+    users.retain(|user_id| !blacklisted.contains(user_id));
+
+    Ok(Recipients {
+        excluded: candidates.len() - users.len(),
+        users,
+    })
+}
+
+// Divides the amount over the recipients, increases the balance for all of them
+// and stores the tip transaction. The tipper is debited, unless the amount was
+// escrowed. The announcement and the DMs to the recipients go through the outbox,
+// so they are only sent once the transaction is committed.
+// This function gets called in `tip role`, `rain` and `reactdrop`
 // We need the ChannelId here because ReactDrops tend to last longer than 15 minutes,
 // which is the time Discord drops the context, giving
 // us an invalid webhook token when trying to send a message using that context.
+#[allow(clippy::too_many_arguments)]
 pub async fn tip_multiple_users(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     author: UserId,
-    http: impl CacheHttp,
     channel_id: &ChannelId,
-    users: Vec<UserId>,
+    guild_id: Option<GuildId>,
+    recipients: Recipients,
     amount: &Amount,
     kind: &str,
+    note: Option<&str>,
+    funding: Funding,
 ) -> Result<(), Error> {
-    // TODO optimize this query (select all that don't exist, insert them in 1 go)
-    // check if all the tippees have an entry in the db
-    // let pool = &ctx.data().database;
-    // let author = ctx.author().id;
-    // let http = ctx.http();
-
-    debug!("users in tip_users: {:?}", users);
-
-    // need to divide tipping amount over number of users
-    // calculation is done using integer division, any remainder is lost,
-    // so we effectively round down the tip amounts.
-    if let Some(div_tip_amount) = amount.checked_div(users.len() as u64) {
-        // we sum it all together again to get a (potentially lower) total amount
-        // to tip
-        let amount = div_tip_amount
-            .checked_mul(users.len() as u64)
-            .unwrap_or(*amount);
-        debug!("after division every member gets {div_tip_amount}");
-        debug!("members: {:#?}", &users);
-
-        let tip_event_id = Uuid::new_v4();
+    let Recipients { users, excluded } = recipients;
+    debug!("users in tip_users: {:?}, {excluded} excluded", users);
 
-        database::process_a_tip(
-            &mut *tx,
-            author,
-            &users,
-            div_tip_amount,
-            &Address::from_str(VRSC_CURRENCY_ID)?,
-        )
-        .await?;
+    if users.is_empty() {
+        error!("could not send tip to group: no recipients");
 
-        database::store_tip_transactions(
-            tx,
-            &tip_event_id,
-            &users,
-            kind,
-            div_tip_amount,
-            author,
-            &Address::from_str(VRSC_CURRENCY_ID)?,
-        )
-        .await?;
+        return Ok(());
+    }
 
-        for user_id in &users {
-            if let Some(Notification::All | Notification::DMOnly) =
-                database::get_loudness_setting(tx, *user_id).await?
-            {
-                user_id
-                    .to_user(&http)
-                    .await?
-                    .dm(
-                        &http,
-                        CreateMessage::new().content(format!(
-                            "You just got tipped {div_tip_amount} from <@{}>!",
-                            &author,
-                        )),
-                    )
-                    .await?;
-            }
+    let tip_event_id = Uuid::new_v4();
+
+    // the remainder of the division goes to randomly picked recipients, one sat each, so the
+    // tipper is debited exactly `amount`. When the amount is smaller than the number of users
+    // in sats, some users end up with nothing and are left out.
+    let mut shares = split_amount(*amount, &users, &tip_event_id);
+    shares.retain(|(_, share)| *share != Amount::ZERO);
+    debug!("shares: {:#?}", &shares);
+
+    match funding {
+        Funding::Balance => {
+            database::process_a_tip(
+                &mut *conn,
+                author,
+                &shares,
+                &Address::from_str(VRSC_CURRENCY_ID)?,
+            )
+            .await?
+        }
+        Funding::Escrow => {
+            database::credit_tippees(conn, &shares, &Address::from_str(VRSC_CURRENCY_ID)?).await?;
         }
+    }
 
-        channel_id
-            .send_message(
-                http,
-                CreateMessage::new().content(format!(
-                    "<@{}> just tipped {} to {} users!",
-                    &author,
-                    amount,
-                    &users.len()
-                )),
+    database::store_tip_transactions(
+        conn,
+        &tip_event_id,
+        &shares,
+        kind,
+        author,
+        &Address::from_str(VRSC_CURRENCY_ID)?,
+        guild_id,
+        note,
+    )
+    .await?;
+
+    let mut receipt = tip_receipt_embed(
+        author,
+        &format!("{} users", shares.len()),
+        *amount,
+        note,
+        &tip_event_id,
+    )
+    .field(
+        "Recipients",
+        mention_list(
+            &shares
+                .iter()
+                .map(|(user_id, _)| *user_id)
+                .collect::<Vec<_>>(),
+        ),
+        false,
+    );
+
+    if excluded > 0 {
+        receipt = receipt.field(
+            "Excluded",
+            format!(
+                "{excluded} (bots, the tipper, suspended or unknown users, or unmet requirements)"
+            ),
+            false,
+        );
+    }
+
+    let event = match kind {
+        "reactdrop" => NotificationEvent::ReactdropWin,
+        _ => NotificationEvent::GroupTip,
+    };
+
+    for (user_id, share) in &shares {
+        if database::get_notification_settings(conn, *user_id)
+            .await?
+            .for_event(event)
+            .wants_dm()
+        {
+            notifier::queue(
+                conn,
+                Recipient::User(*user_id),
+                &CreateMessage::new()
+                    .content(format!(
+                        "You just got tipped {share} from {}!",
+                        pool::mention(author)
+                    ))
+                    .embed(receipt.clone()),
             )
             .await?;
-    } else {
-        error!("could not send tip to role");
+        }
     }
 
+    notifier::queue(
+        conn,
+        Recipient::Channel(*channel_id),
+        &CreateMessage::new().embed(receipt),
+    )
+    .await?;
+
     Ok(())
 }
+
+/// Looks up the users behind `user_ids` all at once. Users that can't be found, e.g. because
+/// their account was deleted, are skipped instead of failing the tip; the number of them is
+/// returned along with the users that were found.
+async fn find_users(
+    ctx: Context<'_>,
+    user_ids: impl IntoIterator<Item = UserId>,
+) -> (Vec<User>, usize) {
+    let lookups = join_all(user_ids.into_iter().map(|user_id| async move {
+        user_id
+            .to_user(ctx)
+            .await
+            .inspect_err(|e| warn!("could not look up user {user_id}: {e}"))
+    }))
+    .await;
+
+    let total = lookups.len();
+    let users = lookups
+        .into_iter()
+        .filter_map(Result::ok)
+        .collect::<Vec<_>>();
+    let unknown = total - users.len();
+
+    (users, unknown)
+}
+
+/// Splits `amount` evenly over `users`. The sats that are left after the division are handed
+/// out one each to recipients picked at random, seeded by the tip id so that the outcome can
+/// be reproduced from the tip id. The shares always add up to `amount`.
+pub fn split_amount(
+    amount: Amount,
+    users: &[UserId],
+    tip_event_id: &Uuid,
+) -> Vec<(UserId, Amount)> {
+    if users.is_empty() {
+        return vec![];
+    }
+
+    let share = amount.as_sat() / users.len() as u64;
+    let remainder = (amount.as_sat() % users.len() as u64) as usize;
+
+    // ChaCha20 gives the same numbers for a seed on every platform and version of rand
+    let mut seed = [0; 32];
+    seed[..16].copy_from_slice(tip_event_id.as_bytes());
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let lucky = rand::seq::index::sample(&mut rng, users.len(), remainder);
+
+    let mut shares = users
+        .iter()
+        .map(|user_id| (*user_id, share))
+        .collect::<Vec<_>>();
+    for i in lucky {
+        shares[i].1 += 1;
+    }
+
+    shares
+        .into_iter()
+        .map(|(user_id, share)| (user_id, Amount::from_sat(share)))
+        .collect()
+}
+
+/// Mentions the users, for as far as they fit in an embed field.
+fn mention_list(users: &[UserId]) -> String {
+    // an embed field value can hold at most 1024 characters
+    const MAX_LENGTH: usize = 1000;
+
+    let mut list = String::new();
+    for (i, user_id) in users.iter().enumerate() {
+        let mention = format!("<@{user_id}> ");
+        if list.len() + mention.len() > MAX_LENGTH {
+            list.push_str(&format!("and {} more", users.len() - i));
+            break;
+        }
+        list.push_str(&mention);
+    }
+
+    list.trim_end().to_string()
+}
+
+/// The public receipt of a tip. Mentions in embeds never ping, so the receipt can be
+/// shown regardless of the notification settings of the recipients.
+fn tip_receipt_embed(
+    tipper: UserId,
+    recipients: &str,
+    amount: Amount,
+    note: Option<&str>,
+    tip_event_id: &Uuid,
+) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .description(format!(
+            "{} just tipped {recipients} {amount}!",
+            pool::mention(tipper)
+        ))
+        .colour(Colour::DARK_GREEN)
+        .footer(CreateEmbedFooter::new(format!("Tip ID: {tip_event_id}")));
+
+    if let Some(note) = note {
+        embed = embed.field("Note", note, false);
+    }
+
+    embed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users(n: u64) -> Vec<UserId> {
+        (1..=n).map(UserId::new).collect()
+    }
+
+    #[test]
+    fn split_amount_adds_up() {
+        let tip_event_id = Uuid::new_v4();
+        let shares = split_amount(Amount::from_sat(1_000_003), &users(7), &tip_event_id);
+
+        assert_eq!(shares.len(), 7);
+        assert_eq!(
+            shares.iter().map(|(_, share)| share.as_sat()).sum::<u64>(),
+            1_000_003
+        );
+        assert!(
+            shares
+                .iter()
+                .all(|(_, share)| [142_857, 142_858].contains(&share.as_sat()))
+        );
+    }
+
+    #[test]
+    fn split_amount_is_deterministic() {
+        let tip_event_id = Uuid::new_v4();
+
+        assert_eq!(
+            split_amount(Amount::from_sat(100), &users(30), &tip_event_id),
+            split_amount(Amount::from_sat(100), &users(30), &tip_event_id)
+        );
+    }
+
+    #[test]
+    fn split_amount_is_reproducible() {
+        let tip_event_id = Uuid::from_u128(0x6f9619ff_8b86_d011_b42d_00c04fc964ff);
+        let lucky = split_amount(Amount::from_sat(3), &users(10), &tip_event_id)
+            .into_iter()
+            .filter(|(_, share)| *share == Amount::from_sat(1))
+            .map(|(user_id, _)| user_id.get())
+            .collect::<Vec<_>>();
+
+        assert_eq!(lucky, vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn split_amount_smaller_than_users() {
+        let shares = split_amount(Amount::from_sat(3), &users(10), &Uuid::new_v4());
+
+        assert_eq!(
+            shares
+                .iter()
+                .filter(|(_, share)| *share == Amount::from_sat(1))
+                .count(),
+            3
+        );
+        assert!(split_amount(Amount::from_sat(3), &[], &Uuid::new_v4()).is_empty());
+    }
+
+    #[test]
+    fn parse_mentions() {
+        assert_eq!(
+            parse_user_mentions("<@123> <@!456>,<@123><@789>"),
+            vec![UserId::new(123), UserId::new(456), UserId::new(789)]
+        );
+    }
+
+    #[test]
+    fn parse_mentions_ignores_other_input() {
+        assert!(parse_user_mentions("").is_empty());
+        assert!(parse_user_mentions("@alice <@&123> <#456> <@abc> <@0> <@123").is_empty());
+    }
+}
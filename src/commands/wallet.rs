@@ -1,10 +1,13 @@
-use std::{ops::Sub, str::FromStr, time::Duration};
+use std::{fmt::Display, ops::Sub, str::FromStr, time::Duration};
 
 use fast_qr::convert::{Builder, Shape, image::ImageBuilder};
 use fast_qr::qr::QRBuilder;
 use poise::CreateReply;
-use poise::serenity_prelude::{CreateAttachment, CreateEmbed};
-use sqlx::{Postgres, Transaction};
+use poise::serenity_prelude::{CreateAttachment, CreateEmbed, CreateMessage, UserId};
+use sqlx::{
+    PgConnection, Postgres, Transaction,
+    types::chrono::{DateTime, Utc},
+};
 use tracing::*;
 use uuid::Uuid;
 use vrsc::{Address, Amount};
@@ -13,8 +16,9 @@ use vrsc_rpc::{
     client::{Client, RpcApi, SendCurrencyOutput},
 };
 
-use crate::commands::user_blacklisted;
-use crate::{Context, Error, VRSC_CURRENCY_ID, database};
+use crate::commands::{misc::NotificationEvent, user_blacklisted};
+use crate::notifier::{self, Recipient};
+use crate::{Context, Error, VRSC_CURRENCY_ID, database, pool};
 
 /// Withdraw funds from the tipbot wallet.
 ///
@@ -32,6 +36,8 @@ use crate::{Context, Error, VRSC_CURRENCY_ID, database};
 /// - an existing VerusID (ends with an `@`)
 ///
 /// A withdrawal fee will be subtracted from the total balance before withdrawal.
+///
+/// Large withdrawals may have to be approved by an admin before they are sent.
 #[instrument(skip(_ctx), fields(request_id = %Uuid::new_v4() ))]
 #[poise::command(slash_command, category = "Wallet", subcommands("amount", "all"))]
 pub async fn withdraw(
@@ -102,32 +108,29 @@ pub async fn all(
                 "withdrawal_amount: {withdrawal_amount}, tx_fee: {withdrawal_fee} must together be balance_amount: {balance_amount}"
             );
 
-            let sco =
-                SendCurrencyOutput::new(None, &withdrawal_amount, &address.to_string(), None, None);
-            let opid = client.send_currency("*", vec![sco], None, None)?;
+            if needs_approval(ctx, withdrawal_amount) {
+                let id =
+                    request_approval(ctx, &mut tx, withdrawal_amount, *withdrawal_fee, &address)
+                        .await?;
+                tx.commit().await?;
 
-            debug!("sendcurrency opid: {:?}", &opid);
+                announce_withdrawal_request(ctx, id, withdrawal_amount, &address).await?;
 
-            if let Some(txid) = wait_for_sendcurrency_finish(&mut tx, client, &opid).await? {
-                let txn = client.get_transaction(&txid, None)?;
-                let tx_fee = txn.fee;
-                // at this point the txid is known. Now blockchain shenanigans could be happening, so we should store everything in the transactions_db table
-                database::store_withdraw_transaction(
-                    &mut tx,
-                    &uuid,
-                    &ctx.author().id,
-                    Some(&txid),
-                    &opid,
-                    withdrawal_fee,
-                    &Address::from_str(VRSC_CURRENCY_ID)?,
-                    withdrawal_amount,
-                    &address,
-                    tx_fee
-                        .map(|fee| Amount::from_vrsc(fee.abs()).unwrap_or(Amount::ZERO))
-                        .unwrap(),
-                )
-                .await?;
+                return Ok(());
+            }
 
+            if let Some(txid) = send_withdrawal(
+                &mut tx,
+                client,
+                &uuid,
+                ctx.author().id,
+                withdrawal_amount,
+                *withdrawal_fee,
+                &address,
+                &Address::from_str(VRSC_CURRENCY_ID)?,
+            )
+            .await?
+            {
                 trace!(
                     "transaction {txid} stored in db, now decrease balance with ({withdrawal_amount} + {withdrawal_fee})"
                 );
@@ -149,6 +152,8 @@ pub async fn all(
 
                 tx.commit().await?;
 
+                queue_withdrawal_dm(ctx, ctx.author().id, withdrawal_amount, &txid).await;
+
                 ctx.send(CreateReply::default().ephemeral(true).embed({
                     let mut embed = CreateEmbed::new()
                         .title("Withdraw")
@@ -181,20 +186,6 @@ pub async fn all(
                 Please contact support with withdrawal ID: {uuid}"
                 );
 
-                database::store_withdraw_transaction(
-                    &mut tx,
-                    &uuid,
-                    &ctx.author().id,
-                    None,
-                    &opid,
-                    withdrawal_fee,
-                    &Address::from_str(VRSC_CURRENCY_ID)?,
-                    withdrawal_amount,
-                    &address,
-                    Amount::ZERO,
-                )
-                .await?;
-
                 tx.commit().await?;
                 ctx.send(CreateReply::default().ephemeral(true).content(&response))
                     .await?;
@@ -286,36 +277,30 @@ pub async fn amount(
         .await?
         .is_some()
     {
-        trace!("balance is sufficient, withdrawal address is valid; starting sendcurrency");
-
-        let sco = SendCurrencyOutput::new(None, &withdrawal_amount, &destination, None, None);
-        let opid = client.send_currency("*", vec![sco], None, None)?;
+        if needs_approval(ctx, withdrawal_amount) {
+            let id =
+                request_approval(ctx, &mut tx, withdrawal_amount, withdrawal_fee, &address).await?;
+            tx.commit().await?;
 
-        debug!("sendcurrency opid: {:?}", &opid);
+            announce_withdrawal_request(ctx, id, withdrawal_amount, &address).await?;
 
-        if let Some(txid) = wait_for_sendcurrency_finish(&mut tx, client, &opid).await? {
-            // let tx_fee = client.get_transaction(&txid, None)?.fee;
-            let txn = client.get_transaction(&txid, None)?;
-            let tx_fee = txn.fee;
+            return Ok(());
+        }
 
-            // at this point the txid is known. Now blockchain shenanigans could be happening,
-            // so we should store everything in the transactions_db table
-            database::store_withdraw_transaction(
-                &mut tx,
-                &uuid,
-                &ctx.author().id,
-                Some(&txid),
-                &opid,
-                &withdrawal_fee,
-                &Address::from_str(VRSC_CURRENCY_ID)?,
-                withdrawal_amount,
-                &address,
-                tx_fee
-                    .map(|fee| Amount::from_vrsc(fee.abs()).unwrap_or(Amount::ZERO))
-                    .unwrap(),
-            )
-            .await?;
+        trace!("balance is sufficient, withdrawal address is valid; starting sendcurrency");
 
+        if let Some(txid) = send_withdrawal(
+            &mut tx,
+            client,
+            &uuid,
+            ctx.author().id,
+            withdrawal_amount,
+            withdrawal_fee,
+            &address,
+            &Address::from_str(VRSC_CURRENCY_ID)?,
+        )
+        .await?
+        {
             trace!("transaction stored, now decrease balance");
             database::decrease_balance(
                 &mut tx,
@@ -333,6 +318,10 @@ pub async fn amount(
             )
             .await?;
 
+            tx.commit().await?;
+
+            queue_withdrawal_dm(ctx, ctx.author().id, withdrawal_amount, &txid).await;
+
             ctx.send(CreateReply::default().ephemeral(true).embed({
                 let mut embed = CreateEmbed::new()
                     .title("Withdraw")
@@ -363,26 +352,12 @@ pub async fn amount(
                 Please contact support with withdrawal ID: {uuid}"
             );
 
-            database::store_withdraw_transaction(
-                &mut tx,
-                &uuid,
-                &ctx.author().id,
-                None,
-                &opid,
-                &withdrawal_fee,
-                &Address::from_str(VRSC_CURRENCY_ID)?,
-                withdrawal_amount,
-                &address,
-                Amount::ZERO,
-            )
-            .await?;
+            tx.commit().await?;
 
             ctx.send(CreateReply::default().ephemeral(true).content(&response))
                 .await?;
         }
 
-        tx.commit().await?;
-
         return Ok(());
     }
 
@@ -546,13 +521,24 @@ pub async fn balance(ctx: Context<'_>) -> Result<(), Error> {
         .unwrap_or(0),
     );
 
-    ctx.send(
-        CreateReply::default()
-            .ephemeral(true)
-            .content(format!("Your balance is: {balance}")),
+    let escrowed = database::get_escrowed_amount(
+        &mut conn,
+        Some(ctx.author().id),
+        &Address::from_str(VRSC_CURRENCY_ID)?,
     )
     .await?;
 
+    let content = if escrowed > Amount::ZERO {
+        format!(
+            "Your balance is: {balance}\nHeld for your reactdrops and scheduled tips: {escrowed}"
+        )
+    } else {
+        format!("Your balance is: {balance}")
+    };
+
+    ctx.send(CreateReply::default().ephemeral(true).content(content))
+        .await?;
+
     Ok(())
 }
 
@@ -597,7 +583,7 @@ pub async fn deposit(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-async fn send_deposit_address_msg(ctx: Context<'_>, address: &Address) -> Result<(), Error> {
+pub async fn send_deposit_address_msg(ctx: Context<'_>, address: &Address) -> Result<(), Error> {
     let qr = QRBuilder::new(address.to_string())
         .build()
         .map_err(|e| format!("QR builder error: {e:?}"))?;
@@ -725,15 +711,28 @@ pub async fn get_and_check_balance(
     ctx: &Context<'_>,
     amount_to_check: Amount,
     tx_fee: Amount,
+) -> Result<Option<Amount>, Error> {
+    get_and_check_balance_of(ctx, ctx.author().id, amount_to_check, tx_fee).await
+}
+
+/// Like `get_and_check_balance`, for an account that is not necessarily the author's, e.g. a
+/// pool the author spends from.
+pub async fn get_and_check_balance_of(
+    ctx: &Context<'_>,
+    account: UserId,
+    amount_to_check: Amount,
+    tx_fee: Amount,
 ) -> Result<Option<Amount>, Error> {
     let mut conn = ctx.data().database.acquire().await?;
 
-    if let Some(balance) = database::get_balance_for_user(
-        &mut conn,
-        ctx.author().id,
-        &Address::from_str(VRSC_CURRENCY_ID)?,
-    )
-    .await?
+    let insufficient = match pool::pool_id(account) {
+        Some(_) => "The balance of the pool is insufficient to tip that amount!",
+        None => "Your balance is insufficient to tip that amount!",
+    };
+
+    if let Some(balance) =
+        database::get_balance_for_user(&mut conn, account, &Address::from_str(VRSC_CURRENCY_ID)?)
+            .await?
     {
         trace!("tipper has balance");
 
@@ -750,7 +749,7 @@ pub async fn get_and_check_balance(
             ctx.send(
                 CreateReply::default()
                     .ephemeral(true)
-                    .content("Your balance is insufficient to tip that amount!".to_string()),
+                    .content(insufficient.to_string()),
             )
             .await?;
 
@@ -758,12 +757,14 @@ pub async fn get_and_check_balance(
         }
     } else {
         trace!("tipper has no balance");
-        warn!("user {} should have a balance!", ctx.author());
+        if pool::pool_id(account).is_none() {
+            warn!("user {account} should have a balance!");
+        }
 
         ctx.send(
             CreateReply::default()
                 .ephemeral(true)
-                .content("Your balance is insufficient to tip that amount!".to_string()),
+                .content(insufficient.to_string()),
         )
         .await?;
 
@@ -771,6 +772,201 @@ pub async fn get_and_check_balance(
     }
 }
 
+/// Sends `amount` to `address` on behalf of `user_id` and stores the withdrawal. When the
+/// sendcurrency doesn't finish, only the operation is stored for support to look into and
+/// `None` is returned.
+///
+/// Nothing is taken from the balance here: the caller does that once the txid is known, or did
+/// it already when the withdrawal had to wait for approval.
+#[allow(clippy::too_many_arguments)]
+pub async fn send_withdrawal(
+    tx: &mut Transaction<'_, Postgres>,
+    client: &Client,
+    uuid: &Uuid,
+    user_id: UserId,
+    amount: Amount,
+    fee: Amount,
+    address: &Address,
+    currency_id: &Address,
+) -> Result<Option<Txid>, Error> {
+    let sco = SendCurrencyOutput::new(None, &amount, &address.to_string(), None, None);
+    let opid = client.send_currency("*", vec![sco], None, None)?;
+
+    debug!("sendcurrency opid: {:?}", &opid);
+
+    let Some(txid) = wait_for_sendcurrency_finish(tx, client, &opid).await? else {
+        database::store_withdraw_transaction(
+            &mut *tx,
+            uuid,
+            &user_id,
+            None,
+            &opid,
+            &fee,
+            currency_id,
+            amount,
+            address,
+            Amount::ZERO,
+        )
+        .await?;
+
+        return Ok(None);
+    };
+
+    let tx_fee = client.get_transaction(&txid, None)?.fee;
+
+    // at this point the txid is known. Now blockchain shenanigans could be happening, so we
+    // should store everything in the transactions_db table
+    database::store_withdraw_transaction(
+        &mut *tx,
+        uuid,
+        &user_id,
+        Some(&txid),
+        &opid,
+        &fee,
+        currency_id,
+        amount,
+        address,
+        tx_fee
+            .map(|fee| Amount::from_vrsc(fee.abs()).unwrap_or(Amount::ZERO))
+            .unwrap(),
+    )
+    .await?;
+
+    Ok(Some(txid))
+}
+
+/// Whether a withdrawal of `amount` has to wait for a treasurer, see
+/// `withdrawal_approval_threshold` in the config.
+fn needs_approval(ctx: Context<'_>, amount: Amount) -> bool {
+    ctx.data()
+        .settings
+        .application
+        .withdrawal_approval_threshold
+        .is_some_and(|threshold| amount > Amount::from_sat(threshold))
+}
+
+/// Takes the amount and the fee from the balance of the author and stores the withdrawal for a
+/// treasurer to approve, letting the admins know. The amount is held until the withdrawal is
+/// sent, or given back when it is rejected.
+///
+/// Returns the id of the request.
+async fn request_approval(
+    ctx: Context<'_>,
+    conn: &mut PgConnection,
+    amount: Amount,
+    fee: Amount,
+    address: &Address,
+) -> Result<i64, Error> {
+    let currency_id = Address::from_str(VRSC_CURRENCY_ID)?;
+
+    database::decrease_balance(&mut *conn, &ctx.author().id, &amount, &fee, &currency_id).await?;
+
+    let id = database::insert_withdrawal_request(
+        conn,
+        ctx.author().id,
+        address,
+        amount,
+        fee,
+        &currency_id,
+    )
+    .await?;
+
+    info!(
+        "withdrawal request #{id} of {amount} by {} needs approval",
+        ctx.author().id
+    );
+
+    notifier::queue(
+        conn,
+        Recipient::Channel(ctx.data().admin_thread()),
+        &CreateMessage::new().content(format!(
+            ":hourglass: withdrawal request #{id}: {amount} from <@{}> to {address}, \
+            use `!approvewithdrawal {id}` or `!rejectwithdrawal {id} <reason>`",
+            ctx.author().id
+        )),
+    )
+    .await?;
+
+    Ok(id)
+}
+
+/// Lets the author know that their withdrawal waits for approval.
+async fn announce_withdrawal_request(
+    ctx: Context<'_>,
+    id: i64,
+    amount: Amount,
+    address: &Address,
+) -> Result<(), Error> {
+    ctx.send(CreateReply::default().ephemeral(true).content(format!(
+        "Withdrawals of this size need to be approved by an admin first. Your request #{id} to \
+        withdraw {amount} to {address} is waiting for approval; the amount and the fee are held \
+        from your balance until then and you'll get a message once it is decided."
+    )))
+    .await?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum WithdrawalRequestStatus {
+    Pending,
+    /// Approved and sent.
+    Sent,
+    Rejected,
+}
+
+impl Display for WithdrawalRequestStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Sent => write!(f, "sent"),
+            Self::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+/// A withdrawal that waits for approval by a treasurer. Its amount and fee are already taken
+/// from the balance of the user.
+#[derive(Debug)]
+pub struct WithdrawalRequest {
+    pub id: i64,
+    pub user_id: UserId,
+    pub destination: Address,
+    pub amount: Amount,
+    pub fee: Amount,
+    pub currency_id: Address,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Puts a DM about a sent withdrawal in the outbox, if the user wants one. The withdrawal is
+/// already committed, so a failure here is logged instead of failing the command.
+pub async fn queue_withdrawal_dm(ctx: Context<'_>, user_id: UserId, amount: Amount, txid: &Txid) {
+    let queued = async {
+        let mut conn = ctx.data().database.acquire().await?;
+
+        if !database::get_notification_settings(&mut conn, user_id)
+            .await?
+            .for_event(NotificationEvent::WithdrawalSent)
+            .wants_dm()
+        {
+            return Ok::<_, Error>(());
+        }
+
+        notifier::queue(
+            &mut conn,
+            Recipient::User(user_id),
+            &CreateMessage::new().content(format!(
+                "Your withdrawal of {amount} has been sent: https://insight.verus.io/tx/{txid}"
+            )),
+        )
+        .await
+    };
+
+    if let Err(e) = queued.await {
+        error!("could not queue the withdrawal DM for {txid} to {user_id}: {e:?}");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
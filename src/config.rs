@@ -1,9 +1,14 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
 
+use poise::ChoiceParameter;
 use secrecy::{ExposeSecret, SecretString};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use vrsc::Amount;
+use vrsc_rpc::client::Client as VerusClient;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
@@ -64,6 +69,33 @@ pub struct ApplicationSettings {
     pub zmq_block_port: u16,
     pub zmq_tx_port: u16,
     pub owners: HashSet<String>,
+    /// Maps Discord role ids in the bot's guild to an admin role. Admin roles can also be
+    /// granted to individual users with `!grantrole`.
+    #[serde(default)]
+    pub admin_roles: HashMap<String, AdminRole>,
+    /// Withdrawals of more than this many sats wait until a treasurer approves them with
+    /// `!approvewithdrawal`. Without it, every withdrawal is sent right away.
+    #[serde(default)]
+    pub withdrawal_approval_threshold: Option<u64>,
+    /// Users who turned price alerts on get a DM when the USD price of VRSC moved by at least
+    /// this many percent since the last alert. Without it, no price alerts are sent.
+    #[serde(default)]
+    pub price_alert_percent: Option<f64>,
+}
+
+impl ApplicationSettings {
+    /// A client for the RPC interface of the local Verus daemon.
+    pub fn verus_client(&self) -> Result<VerusClient, crate::Error> {
+        VerusClient::vrsc(
+            self.testnet,
+            vrsc_rpc::Auth::UserPass(
+                format!("http://127.0.0.1:{}", self.rpc_port),
+                self.rpc_user.clone(),
+                self.rpc_password.clone(),
+            ),
+        )
+        .map_err(|e| e.into())
+    }
 }
 
 pub fn get_configuration() -> Result<Config, config::ConfigError> {
@@ -123,3 +155,29 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+/// The permission level of an admin. Every role includes the permissions of the roles below it:
+/// a treasurer can do everything support can, a superadmin can do everything.
+///
+/// Owners from the config are always superadmin.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ChoiceParameter, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminRole {
+    /// Can look up transactions and balances.
+    #[name = "support"]
+    Support,
+    /// Can change fees, approve withdrawals and move funds.
+    #[name = "treasurer"]
+    Treasurer,
+    /// Can change the state of the bot and manage other admins.
+    #[name = "superadmin"]
+    Superadmin,
+}
+
+impl Display for AdminRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -2,13 +2,28 @@ use std::str::FromStr;
 
 use crate::{
     Error,
-    commands::misc::Notification,
-    reactdrop::{Reactdrop, ReactdropState},
+    airdrop::{Airdrop, AirdropRun, AirdropRunStatus},
+    commands::{
+        admin::AuditEntry,
+        history::{HistoryEntry, HistoryKind},
+        misc::{Notification, NotificationEvent, NotificationSettings},
+        stats::{LeaderboardEntry, TipStats},
+        tipping::Recipients,
+        wallet::{WithdrawalRequest, WithdrawalRequestStatus},
+    },
+    config::AdminRole,
+    notifier::{OutboxMessage, OutboxStatus, Recipient},
+    pool::Pool,
+    reactdrop::{Reactdrop, ReactdropState, Requirements},
+    scheduler::{ScheduledTip, ScheduledTipStatus},
 };
 use num_traits::cast::ToPrimitive;
-use poise::serenity_prelude::UserId;
+use poise::{
+    ChoiceParameter,
+    serenity_prelude::{ChannelId, GuildId, RoleId, UserId},
+};
 use sqlx::{
-    PgConnection, Postgres, Transaction,
+    PgConnection,
     types::chrono::{DateTime, Utc},
 };
 use tracing::*;
@@ -32,25 +47,29 @@ use vrsc_rpc::bitcoin::Txid;
 
 // to store multiple tip transactions at once. Usually when a group tip needs to be processed.
 // Because it all ends up as a transaction, it's fine to have multiple INSERT statements.
+#[allow(clippy::too_many_arguments)]
 pub async fn store_tip_transactions(
     conn: &mut PgConnection,
     uuid: &Uuid,
-    tippees: &Vec<UserId>,
+    tippees: &[(UserId, Amount)],
     kind: &str,
-    amount: Amount,
-    tipper: UserId, // this is always a user
+    tipper: UserId, // this is always a user or a pool
     currency_id: &Address,
+    guild_id: Option<GuildId>,
+    note: Option<&str>,
 ) -> Result<(), Error> {
-    for tippee in tippees {
+    for (tippee, amount) in tippees {
         sqlx::query!(
-            "INSERT INTO tips (uuid, currency_id, discord_id, kind, amount, counterparty)
-            VALUES ($1, $2, $3, $4, $5, $6)",
+            "INSERT INTO tips (uuid, currency_id, discord_id, kind, amount, counterparty, guild_id, note)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
             uuid.to_string(),
             currency_id.to_string(),
             tippee.get() as i64,
             kind,
             amount.as_sat() as i64,
             tipper.get() as i64,
+            guild_id.map(|guild_id| guild_id.get() as i64),
+            note,
         )
         .execute(&mut *conn)
         .await?;
@@ -87,14 +106,42 @@ pub async fn get_balance_for_user(
 // The tipper can tip himself.
 // This function both increases the balances for the tip receivers and decreases the balance of the tipper.
 // If one of these 2 actions fail, the database is not updated.
+/// Credits every tippee with their amount and debits the tipper with the sum of it all.
 pub async fn process_a_tip(
-    tx: &mut Transaction<'_, Postgres>,
+    conn: &mut PgConnection,
     tipper: UserId,
-    tippees: &[UserId],
-    amount: Amount,
+    tippees: &[(UserId, Amount)],
     currency_id: &Address,
 ) -> Result<(), Error> {
-    for tippee in tippees {
+    let total = credit_tippees(conn, tippees, currency_id).await?;
+
+    sqlx::query!(
+        "UPDATE balances 
+        SET balance = balance - $1
+        WHERE discord_id = $2 AND 
+        currency_id = $3",
+        total as i64,
+        tipper.get() as i64,
+        currency_id.to_string()
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    trace!("decreased balances");
+
+    Ok(())
+}
+
+/// Increases the balance of every tippee and returns the total that was credited. The caller is
+/// responsible for taking that total from somewhere.
+pub async fn credit_tippees(
+    conn: &mut PgConnection,
+    tippees: &[(UserId, Amount)],
+    currency_id: &Address,
+) -> Result<u64, Error> {
+    let mut total: u64 = 0;
+
+    for (tippee, amount) in tippees {
         sqlx::query!(
             "INSERT INTO balances (currency_id, discord_id, balance)
             VALUES ($1, $2, $3)
@@ -105,28 +152,15 @@ pub async fn process_a_tip(
             tippee.get() as i64,
             amount.as_sat() as i64
         )
-        .execute(&mut **tx)
-        .await?;
-    }
-
-    if let Some(mul) = amount.checked_mul(tippees.len() as u64) {
-        sqlx::query!(
-            "UPDATE balances 
-            SET balance = balance - $1
-            WHERE discord_id = $2 AND 
-            currency_id = $3",
-            mul.as_sat() as i64,
-            tipper.get() as i64,
-            currency_id.to_string()
-        )
-        .execute(&mut **tx)
+        .execute(&mut *conn)
         .await?;
 
-        trace!("decreased balances");
-        return Ok(());
+        total = total
+            .checked_add(amount.as_sat())
+            .ok_or("total tip amount overflows")?;
     }
 
-    Ok(())
+    Ok(total)
 }
 
 pub async fn store_new_address_for_user(
@@ -344,6 +378,41 @@ pub async fn store_withdraw_transaction(
     Ok(())
 }
 
+/// Stores a manual balance adjustment by an admin. `amount` is negative for debits.
+pub async fn store_adjustment_transaction(
+    conn: &mut PgConnection,
+    uuid: &Uuid,
+    user_id: &UserId,
+    currency_id: &Address,
+    amount: i64,
+    issued_by: &UserId,
+    reason: &str,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO transactions (
+            uuid,
+            discord_id,
+            transaction_id,
+            transaction_action,
+            currency_id,
+            amount,
+            address,
+            issued_by,
+            reason
+        ) VALUES ($1, $2, '', 'adjustment', $3, $4, '', $5, $6)",
+        uuid.to_string(),
+        user_id.get() as i64,
+        currency_id.to_string(),
+        amount,
+        issued_by.get() as i64,
+        reason
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 pub async fn store_opid(
     conn: &mut PgConnection,
@@ -390,18 +459,68 @@ pub async fn update_notifications(
     Ok(())
 }
 
-pub async fn get_loudness_setting(
+pub async fn get_notification_settings(
     conn: &mut PgConnection,
     user_id: UserId,
-) -> Result<Option<Notification>, Error> {
+) -> Result<NotificationSettings, Error> {
     let row = sqlx::query!(
-        "SELECT loudness FROM notifications WHERE discord_id = $1",
+        "SELECT loudness, deposit_confirmed, withdrawal_sent, direct_tip, group_tip, reactdrop_win, price_alert
+        FROM notifications WHERE discord_id = $1",
         user_id.get() as i64
     )
     .fetch_optional(conn)
     .await?;
 
-    Ok(row.and_then(|r| r.loudness.map(Notification::from)))
+    Ok(row
+        .map(|r| NotificationSettings {
+            general: r.loudness.map(Notification::from),
+            deposit_confirmed: r.deposit_confirmed.map(Notification::from),
+            withdrawal_sent: r.withdrawal_sent.map(Notification::from),
+            direct_tip: r.direct_tip.map(Notification::from),
+            group_tip: r.group_tip.map(Notification::from),
+            reactdrop_win: r.reactdrop_win.map(Notification::from),
+            price_alert: r.price_alert.map(Notification::from),
+        })
+        .unwrap_or_default())
+}
+
+/// The users who want price alerts in DM. Price alerts are off unless a user turns them on.
+pub async fn get_price_alert_subscribers(conn: &mut PgConnection) -> Result<Vec<UserId>, Error> {
+    let rows = sqlx::query!(
+        "SELECT discord_id FROM notifications WHERE price_alert IN ('All', 'DM only')"
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| UserId::new(row.discord_id as u64))
+        .collect())
+}
+
+/// Sets (or with `None`, resets) the setting for `event`. An `event` of `None` is the general
+/// setting for all tips.
+pub async fn update_event_notification(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    event: Option<NotificationEvent>,
+    setting: Option<&Notification>,
+) -> Result<(), Error> {
+    // the column name comes from a fixed set of names, never from user input.
+    let column = event.map_or("loudness", |event| event.column());
+
+    sqlx::query(&format!(
+        "INSERT INTO notifications (discord_id, {column})
+        VALUES ($1, $2)
+        ON CONFLICT (discord_id)
+        DO UPDATE SET {column} = $2"
+    ))
+    .bind(user_id.get() as i64)
+    .bind(setting.map(|setting| setting.to_string()))
+    .execute(conn)
+    .await?;
+
+    Ok(())
 }
 
 pub async fn get_blacklist_status(
@@ -419,6 +538,29 @@ pub async fn get_blacklist_status(
     Ok(is_blacklisted)
 }
 
+/// Returns the users in `user_ids` that are currently blacklisted.
+pub async fn get_blacklisted_users(
+    conn: &mut PgConnection,
+    user_ids: &[UserId],
+) -> Result<Vec<UserId>, Error> {
+    let user_ids = user_ids
+        .iter()
+        .map(|user_id| user_id.get() as i64)
+        .collect::<Vec<_>>();
+
+    let rows = sqlx::query!(
+        "SELECT discord_id FROM blacklist WHERE blacklisted AND discord_id = ANY($1)",
+        &user_ids
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| UserId::new(r.discord_id as u64))
+        .collect())
+}
+
 pub async fn set_blacklist_status(
     conn: &mut PgConnection,
     user_id: UserId,
@@ -519,17 +661,23 @@ pub async fn get_largest_tip(conn: &mut PgConnection, currency_id: &Address) ->
 pub async fn insert_reactdrop(
     conn: &mut PgConnection,
     author: i64,
+    spender: Option<i64>,
     emoji: String,
     amount: i64,
     channel_id: i64,
     message_id: i64,
     finish_time: DateTime<Utc>,
     currency_id: &Address,
+    guild_id: Option<i64>,
+    winners: Option<u32>,
+    draw_height: Option<u64>,
+    requirements: &Requirements,
 ) -> Result<(), Error> {
     sqlx::query!(
         "INSERT INTO reactdrops
-        (author, channel_id, message_id, finish_time, emojistr, amount, status, currency_id)
-        VALUES ($1, $2, $3, $4, $5, $6, 'pending', $7)
+        (author, channel_id, message_id, finish_time, emojistr, amount, status, currency_id, guild_id, winners, escrowed,
+        required_role_id, min_account_age_days, min_membership_days, max_participants, draw_height, spender)
+        VALUES ($1, $2, $3, $4, $5, $6, 'pending', $7, $8, $9, TRUE, $10, $11, $12, $13, $14, $15)
         ON CONFLICT (channel_id, message_id)
         DO NOTHING",
         author,
@@ -538,7 +686,15 @@ pub async fn insert_reactdrop(
         finish_time,
         emoji,
         amount,
-        currency_id.to_string()
+        currency_id.to_string(),
+        guild_id,
+        winners.map(|winners| winners as i32),
+        requirements.role.map(|role| role.get() as i64),
+        requirements.min_account_age_days.map(|days| days as i32),
+        requirements.min_membership_days.map(|days| days as i32),
+        requirements.max_participants.map(|max| max as i32),
+        draw_height.map(|height| height as i64),
+        spender
     )
     .execute(conn)
     .await?;
@@ -548,38 +704,157 @@ pub async fn insert_reactdrop(
 
 /// Returns pending reactdrops, or an emtpy Vec if no pending reactdrops present
 pub async fn get_pending_reactdrops(conn: &mut PgConnection) -> Result<Vec<Reactdrop>, Error> {
+    query_pending_reactdrops(conn, None).await
+}
+
+// Returns the pending reactdrops, only the one with the given id if there is one.
+async fn query_pending_reactdrops(
+    conn: &mut PgConnection,
+    id: Option<i64>,
+) -> Result<Vec<Reactdrop>, Error> {
     let rows = sqlx::query!(
         "SELECT *
         FROM reactdrops
-        WHERE status = 'pending'"
+        WHERE status = 'pending' AND ($1::bigint IS NULL OR id = $1)",
+        id
     )
     .fetch_all(conn)
     .await?;
 
-    let vec: Vec<Reactdrop> = rows
+    let vec = rows
         .into_iter()
-        .map(|row| Reactdrop {
-            status: crate::reactdrop::ReactdropState::Pending,
-            author: (row.author as u64).into(),
-            emoji: row.emojistr,
-            tip_amount: Amount::from_sat(row.amount as u64),
-            channel_id: (row.channel_id as u64).into(),
-            message_id: (row.message_id as u64).into(),
-            finish_time: row.finish_time,
+        .map(|row| {
+            Ok(Reactdrop {
+                id: row.id,
+                status: ReactdropState::try_from(row.status)?,
+                author: (row.author as u64).into(),
+                spender: row.spender.map(|spender| (spender as u64).into()),
+                emoji: row.emojistr,
+                tip_amount: Amount::from_sat(row.amount as u64),
+                channel_id: (row.channel_id as u64).into(),
+                message_id: (row.message_id as u64).into(),
+                guild_id: row.guild_id.map(|guild_id| (guild_id as u64).into()),
+                finish_time: row.finish_time,
+                winners: row.winners.map(|winners| winners as u32),
+                escrowed: row.escrowed,
+                requirements: Requirements {
+                    role: row.required_role_id.map(|role| (role as u64).into()),
+                    min_account_age_days: row.min_account_age_days.map(|days| days as u32),
+                    min_membership_days: row.min_membership_days.map(|days| days as u32),
+                    max_participants: row.max_participants.map(|max| max as u32),
+                },
+                draw_height: row.draw_height.map(|height| height as u64),
+                entrants: row.entrants.map(|entrants| Recipients {
+                    users: entrants.into_iter().map(|id| (id as u64).into()).collect(),
+                    excluded: row.excluded_entrants.unwrap_or_default() as usize,
+                }),
+                next_attempt_at: row.next_attempt_at,
+            })
         })
-        .collect();
+        .collect::<Result<Vec<_>, Error>>()?;
 
     Ok(vec)
 }
 
-pub async fn update_reactdrop(
+/// Closes the entries of a pending giveaway reactdrop: stores who participated and the block
+/// whose hash the winners are drawn with. Returns false when the reactdrop was not pending anymore.
+pub async fn close_reactdrop_entries(
+    conn: &mut PgConnection,
+    id: i64,
+    entrants: &Recipients,
+    draw_height: u64,
+) -> Result<bool, Error> {
+    let users = entrants
+        .users
+        .iter()
+        .map(|user| user.get() as i64)
+        .collect::<Vec<_>>();
+
+    let result = sqlx::query!(
+        "UPDATE reactdrops SET entrants = $2, excluded_entrants = $3, draw_height = $4
+        WHERE id = $1 AND status = 'pending' AND entrants IS NULL",
+        id,
+        &users,
+        entrants.excluded as i32,
+        draw_height as i64
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Cancels a pending reactdrop. Returns false when the reactdrop was not pending anymore, or
+/// when it is a giveaway whose entries are closed: the winners are about to be drawn then.
+pub async fn cancel_reactdrop(conn: &mut PgConnection, id: i64) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE reactdrops SET status = 'cancelled'
+        WHERE id = $1 AND status = 'pending' AND entrants IS NULL",
+        id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Stores the seed that was used to draw the winners of a giveaway reactdrop.
+pub async fn set_reactdrop_draw_seed(
     conn: &mut PgConnection,
     channel_id: i64,
     message_id: i64,
-    status: ReactdropState,
+    draw_seed: &str,
 ) -> Result<(), Error> {
     sqlx::query!(
-        "UPDATE reactdrops SET status = $3 WHERE channel_id = $1 AND message_id = $2",
+        "UPDATE reactdrops SET draw_seed = $3 WHERE channel_id = $1 AND message_id = $2",
+        channel_id,
+        message_id,
+        draw_seed
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Sums the amounts that are held for pending reactdrops and for scheduled tips that were not
+/// carried out yet, optionally only the ones of `author`.
+pub async fn get_escrowed_amount(
+    conn: &mut PgConnection,
+    author: Option<UserId>,
+    currency_id: &Address,
+) -> Result<Amount, Error> {
+    let record = sqlx::query!(
+        r#"SELECT COALESCE(SUM(amount), 0) AS "sum!"
+        FROM (
+            SELECT amount FROM reactdrops
+            WHERE status = 'pending' AND escrowed AND currency_id = $1
+            AND ($2::bigint IS NULL OR author = $2)
+            UNION ALL
+            SELECT amount FROM scheduled_tips
+            WHERE status IN ('pending', 'running') AND currency_id = $1
+            AND ($2::bigint IS NULL OR author = $2)
+        ) AS held"#,
+        currency_id.to_string(),
+        author.map(|author| author.get() as i64)
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(Amount::from_sat(record.sum.to_u64().unwrap_or_default()))
+}
+
+/// Moves a pending reactdrop to `status`. Returns false when the reactdrop was not pending
+/// anymore, e.g. because it was cancelled in the meantime.
+pub async fn update_reactdrop(
+    conn: &mut PgConnection,
+    channel_id: i64,
+    message_id: i64,
+    status: ReactdropState,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE reactdrops SET status = $3
+        WHERE channel_id = $1 AND message_id = $2 AND status = 'pending'",
         channel_id,
         message_id,
         status.to_string()
@@ -587,9 +862,72 @@ pub async fn update_reactdrop(
     .execute(conn)
     .await?;
 
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn get_pending_reactdrop(
+    conn: &mut PgConnection,
+    id: i64,
+) -> Result<Option<Reactdrop>, Error> {
+    Ok(query_pending_reactdrops(conn, Some(id)).await?.pop())
+}
+
+/// Counts a failed attempt to end a pending reactdrop and returns the number of attempts so far,
+/// or `None` when the reactdrop is not pending anymore.
+pub async fn record_reactdrop_failure(
+    conn: &mut PgConnection,
+    id: i64,
+    error: &str,
+) -> Result<Option<i32>, Error> {
+    let row = sqlx::query!(
+        "UPDATE reactdrops SET attempts = attempts + 1, last_error = $2
+        WHERE id = $1 AND status = 'pending'
+        RETURNING attempts",
+        id,
+        error
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| row.attempts))
+}
+
+/// Tries to end a pending reactdrop that failed to end again at `next_attempt_at`.
+pub async fn retry_reactdrop(
+    conn: &mut PgConnection,
+    id: i64,
+    next_attempt_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE reactdrops SET next_attempt_at = $2 WHERE id = $1 AND status = 'pending'",
+        id,
+        next_attempt_at
+    )
+    .execute(conn)
+    .await?;
+
     Ok(())
 }
 
+/// Moves the end of a pending reactdrop. Returns false when the reactdrop was not pending anymore,
+/// or when its entries are closed.
+pub async fn set_reactdrop_finish_time(
+    conn: &mut PgConnection,
+    id: i64,
+    finish_time: DateTime<Utc>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE reactdrops SET finish_time = $2
+        WHERE id = $1 AND status = 'pending' AND entrants IS NULL",
+        id,
+        finish_time
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
 pub async fn get_summed_deposits(conn: &mut PgConnection) -> Result<Amount, Error> {
     let row = sqlx::query!(
         r#"
@@ -621,3 +959,981 @@ pub async fn get_summed_withdrawals(conn: &mut PgConnection) -> Result<Amount, E
 
     Ok(Amount::from_sat(amount))
 }
+
+/// Returns the sum of all balance adjustments by admins in a currency in sats, negative when more
+/// was debited than credited.
+pub async fn get_summed_adjustments(
+    conn: &mut PgConnection,
+    currency_id: &Address,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COALESCE(SUM(amount), 0) as "amount!"
+        FROM transactions
+        WHERE transaction_action = 'adjustment' AND currency_id = $1
+        "#,
+        currency_id.to_string()
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.amount.to_i64().unwrap_or_default())
+}
+
+/// Stores an admin action in the audit log and returns the id of the new entry.
+pub async fn insert_admin_audit(
+    conn: &mut PgConnection,
+    admin: UserId,
+    action: &str,
+    arguments: &str,
+    old_value: Option<&str>,
+    new_value: Option<&str>,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO admin_audit (discord_id, action, arguments, old_value, new_value)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id",
+        admin.get() as i64,
+        action,
+        arguments,
+        old_value,
+        new_value
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.id)
+}
+
+/// Returns a page of the audit log, newest entries first.
+pub async fn get_admin_audit(
+    conn: &mut PgConnection,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<AuditEntry>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, discord_id, action, arguments, old_value, new_value, created_at
+        FROM admin_audit
+        ORDER BY id DESC
+        LIMIT $1 OFFSET $2",
+        limit,
+        offset
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| AuditEntry {
+            id: row.id,
+            admin: UserId::new(row.discord_id as u64),
+            action: row.action,
+            arguments: row.arguments,
+            old_value: row.old_value,
+            new_value: row.new_value,
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+pub async fn get_admin_role(
+    conn: &mut PgConnection,
+    user_id: UserId,
+) -> Result<Option<AdminRole>, Error> {
+    let role = sqlx::query!(
+        "SELECT role FROM admin_roles WHERE discord_id = $1",
+        user_id.get() as i64
+    )
+    .fetch_optional(conn)
+    .await?
+    .and_then(|row| AdminRole::from_name(&row.role));
+
+    Ok(role)
+}
+
+pub async fn set_admin_role(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    role: AdminRole,
+    granted_by: UserId,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO admin_roles (discord_id, role, granted_by)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (discord_id)
+        DO UPDATE SET
+        role = excluded.role,
+        granted_by = excluded.granted_by",
+        user_id.get() as i64,
+        role.name(),
+        granted_by.get() as i64
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn remove_admin_role(conn: &mut PgConnection, user_id: UserId) -> Result<(), Error> {
+    sqlx::query!(
+        "DELETE FROM admin_roles WHERE discord_id = $1",
+        user_id.get() as i64
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the full history of a user: deposits, withdrawals, adjustments, tips sent and
+/// tips received, newest first.
+///
+/// A group tip is stored as one row per recipient, so tips sent are grouped by tip event.
+pub async fn get_history(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    kind: Option<HistoryKind>,
+    currency_id: Option<&Address>,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<HistoryEntry>, Error> {
+    let rows = sqlx::query!(
+        r#"SELECT
+            kind AS "kind!",
+            created_at AS "created_at!",
+            amount AS "amount!",
+            currency_id AS "currency_id!",
+            counterparty,
+            recipients AS "recipients!",
+            txid,
+            fee,
+            uuid AS "uuid!"
+        FROM (
+            SELECT
+                CASE transaction_action WHEN 'withdraw' THEN 'withdraw' WHEN 'adjustment' THEN 'adjustment' ELSE 'deposit' END AS kind,
+                created_at,
+                amount,
+                currency_id,
+                NULL::bigint AS counterparty,
+                1::bigint AS recipients,
+                NULLIF(transaction_id, '') AS txid,
+                fee,
+                uuid
+            FROM transactions
+            WHERE discord_id = $1
+            UNION ALL
+            SELECT
+                CASE kind WHEN 'reactdrop' THEN 'reactdrop' ELSE 'tip_received' END,
+                created_at,
+                amount,
+                currency_id,
+                counterparty,
+                1,
+                NULL,
+                NULL,
+                uuid
+            FROM tips
+            WHERE discord_id = $1
+            UNION ALL
+            SELECT
+                'tip_sent',
+                MIN(created_at),
+                SUM(amount)::bigint,
+                currency_id,
+                CASE COUNT(*) WHEN 1 THEN MIN(discord_id) END,
+                COUNT(*),
+                NULL,
+                NULL,
+                uuid
+            FROM tips
+            WHERE counterparty = $1
+            GROUP BY uuid, currency_id
+            UNION ALL
+            -- held until the reactdrop or scheduled tip is paid out, which shows up as tips sent
+            SELECT 'held', created_at, amount, currency_id, NULL, 1, NULL, NULL, 'reactdrop #' || id
+            FROM reactdrops
+            WHERE author = $1 AND escrowed AND status = 'pending'
+            UNION ALL
+            SELECT 'held', created_at, amount, currency_id, NULL, 1, NULL, NULL, 'scheduled #' || id
+            FROM scheduled_tips
+            WHERE author = $1 AND status IN ('pending', 'running')
+        ) history
+        WHERE ($2::text IS NULL OR kind = $2)
+            AND ($3::text IS NULL OR currency_id = $3)
+        ORDER BY created_at DESC
+        LIMIT $4 OFFSET $5"#,
+        user_id.get() as i64,
+        kind.map(|kind| kind.as_sql()),
+        currency_id.map(|currency_id| currency_id.to_string()),
+        limit,
+        offset
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            Some(HistoryEntry {
+                kind: HistoryKind::from_sql(&row.kind)?,
+                created_at: row.created_at,
+                amount: row.amount,
+                currency_id: Address::from_str(&row.currency_id).ok()?,
+                counterparty: row.counterparty.map(|id| UserId::new(id as u64)),
+                recipients: row.recipients,
+                txid: row.txid,
+                fee: row.fee.map(|fee| Amount::from_sat(fee as u64)),
+                id: row.uuid,
+            })
+        })
+        .collect())
+}
+
+/// Returns the users that tipped the most in a guild since the given time.
+pub async fn get_top_tippers(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+    since: Option<DateTime<Utc>>,
+    currency_id: &Address,
+    limit: i64,
+) -> Result<Vec<LeaderboardEntry>, Error> {
+    let rows = sqlx::query!(
+        r#"SELECT counterparty AS "user_id!", SUM(amount)::bigint AS "total!", COUNT(DISTINCT uuid) AS "count!"
+        FROM tips
+        WHERE guild_id = $1
+            AND currency_id = $2
+            AND ($3::timestamptz IS NULL OR created_at >= $3)
+        GROUP BY counterparty
+        ORDER BY 2 DESC
+        LIMIT $4"#,
+        guild_id.get() as i64,
+        currency_id.to_string(),
+        since,
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| LeaderboardEntry {
+            user_id: UserId::new(row.user_id as u64),
+            total: Amount::from_sat(row.total as u64),
+            count: row.count,
+        })
+        .collect())
+}
+
+/// Returns the users that received the most tips in a guild since the given time.
+pub async fn get_top_receivers(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+    since: Option<DateTime<Utc>>,
+    currency_id: &Address,
+    limit: i64,
+) -> Result<Vec<LeaderboardEntry>, Error> {
+    let rows = sqlx::query!(
+        r#"SELECT discord_id AS "user_id!", SUM(amount)::bigint AS "total!", COUNT(*) AS "count!"
+        FROM tips
+        WHERE guild_id = $1
+            AND currency_id = $2
+            AND ($3::timestamptz IS NULL OR created_at >= $3)
+        GROUP BY discord_id
+        ORDER BY 2 DESC
+        LIMIT $4"#,
+        guild_id.get() as i64,
+        currency_id.to_string(),
+        since,
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| LeaderboardEntry {
+            user_id: UserId::new(row.user_id as u64),
+            total: Amount::from_sat(row.total as u64),
+            count: row.count,
+        })
+        .collect())
+}
+
+/// Returns the tipping statistics of a user over all guilds.
+pub async fn get_tip_stats(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    currency_id: &Address,
+) -> Result<TipStats, Error> {
+    let row = sqlx::query!(
+        r#"SELECT
+            COALESCE((SELECT SUM(amount) FROM tips WHERE counterparty = $1 AND currency_id = $2), 0)::bigint AS "sent!",
+            (SELECT COUNT(DISTINCT uuid) FROM tips WHERE counterparty = $1 AND currency_id = $2) AS "sent_count!",
+            COALESCE((
+                SELECT MAX(total) FROM (
+                    SELECT SUM(amount) AS total
+                    FROM tips
+                    WHERE counterparty = $1 AND currency_id = $2
+                    GROUP BY uuid
+                ) events
+            ), 0)::bigint AS "largest_sent!",
+            COALESCE((SELECT SUM(amount) FROM tips WHERE discord_id = $1 AND currency_id = $2), 0)::bigint AS "received!",
+            (SELECT COUNT(*) FROM tips WHERE discord_id = $1 AND currency_id = $2) AS "received_count!",
+            COALESCE((SELECT MAX(amount) FROM tips WHERE discord_id = $1 AND currency_id = $2), 0)::bigint AS "largest_received!",
+            (SELECT COUNT(*) FROM tips WHERE discord_id = $1 AND kind = 'reactdrop') AS "reactdrops_joined!""#,
+        user_id.get() as i64,
+        currency_id.to_string()
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(TipStats {
+        sent: Amount::from_sat(row.sent as u64),
+        sent_count: row.sent_count,
+        largest_sent: Amount::from_sat(row.largest_sent as u64),
+        received: Amount::from_sat(row.received as u64),
+        received_count: row.received_count,
+        largest_received: Amount::from_sat(row.largest_received as u64),
+        reactdrops_joined: row.reactdrops_joined,
+    })
+}
+
+pub async fn insert_outbox_message(
+    conn: &mut PgConnection,
+    recipient: Recipient,
+    payload: &str,
+) -> Result<(), Error> {
+    let (discord_id, channel_id) = match recipient {
+        Recipient::User(user_id) => (Some(user_id.get() as i64), None),
+        Recipient::Channel(channel_id) => (None, Some(channel_id.get() as i64)),
+    };
+
+    sqlx::query!(
+        "INSERT INTO outbox (discord_id, channel_id, payload) VALUES ($1, $2, $3)",
+        discord_id,
+        channel_id,
+        payload
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the pending outbox messages that are due, oldest first.
+pub async fn get_due_outbox_messages(
+    conn: &mut PgConnection,
+    limit: i64,
+) -> Result<Vec<OutboxMessage>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, discord_id, channel_id, payload, attempts
+        FROM outbox
+        WHERE status = 'pending' AND next_attempt_at <= now()
+        ORDER BY id
+        LIMIT $1",
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let recipient = match (row.discord_id, row.channel_id) {
+                (Some(discord_id), _) => Recipient::User(UserId::new(discord_id as u64)),
+                (None, Some(channel_id)) => Recipient::Channel(ChannelId::new(channel_id as u64)),
+                // prevented by a constraint on the table
+                (None, None) => return None,
+            };
+
+            Some(OutboxMessage {
+                id: row.id,
+                recipient,
+                payload: row.payload,
+                attempts: row.attempts,
+            })
+        })
+        .collect())
+}
+
+pub async fn update_outbox_message(
+    conn: &mut PgConnection,
+    id: i64,
+    status: OutboxStatus,
+    attempts: i32,
+    last_error: Option<&str>,
+    next_attempt_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE outbox
+        SET status = $2,
+            attempts = $3,
+            last_error = COALESCE($4, last_error),
+            next_attempt_at = $5,
+            delivered_at = CASE WHEN $2 = 'delivered' THEN now() END
+        WHERE id = $1",
+        id,
+        status.to_string(),
+        attempts,
+        last_error,
+        next_attempt_at
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_scheduled_tip(
+    conn: &mut PgConnection,
+    author: UserId,
+    spender: Option<UserId>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    amount: Amount,
+    currency_id: &Address,
+    action: &str,
+    run_at: DateTime<Utc>,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO scheduled_tips (author, guild_id, channel_id, amount, currency_id, action, run_at, spender)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id",
+        author.get() as i64,
+        guild_id.get() as i64,
+        channel_id.get() as i64,
+        amount.as_sat() as i64,
+        currency_id.to_string(),
+        action,
+        run_at,
+        spender.map(|spender| spender.get() as i64)
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.id)
+}
+
+pub async fn get_due_scheduled_tips(conn: &mut PgConnection) -> Result<Vec<ScheduledTip>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, author, spender, guild_id, channel_id, amount, action, run_at
+        FROM scheduled_tips
+        WHERE status = 'pending' AND run_at <= now()
+        ORDER BY run_at"
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ScheduledTip {
+                id: row.id,
+                author: UserId::new(row.author as u64),
+                spender: row.spender.map(|spender| UserId::new(spender as u64)),
+                guild_id: GuildId::new(row.guild_id as u64),
+                channel_id: ChannelId::new(row.channel_id as u64),
+                amount: Amount::from_sat(row.amount as u64),
+                action: serde_json::from_str(&row.action)?,
+                run_at: row.run_at,
+            })
+        })
+        .collect()
+}
+
+/// Returns the scheduled tips of a guild that are waiting to be carried out, first due first.
+pub async fn get_pending_scheduled_tips(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+) -> Result<Vec<ScheduledTip>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, author, spender, guild_id, channel_id, amount, action, run_at
+        FROM scheduled_tips
+        WHERE status = 'pending' AND guild_id = $1
+        ORDER BY run_at",
+        guild_id.get() as i64
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ScheduledTip {
+                id: row.id,
+                author: UserId::new(row.author as u64),
+                spender: row.spender.map(|spender| UserId::new(spender as u64)),
+                guild_id: GuildId::new(row.guild_id as u64),
+                channel_id: ChannelId::new(row.channel_id as u64),
+                amount: Amount::from_sat(row.amount as u64),
+                action: serde_json::from_str(&row.action)?,
+                run_at: row.run_at,
+            })
+        })
+        .collect()
+}
+
+/// Returns the scheduled tip with `id` if it is still waiting to be carried out.
+pub async fn get_pending_scheduled_tip(
+    conn: &mut PgConnection,
+    id: i64,
+) -> Result<Option<ScheduledTip>, Error> {
+    let row = sqlx::query!(
+        "SELECT id, author, spender, guild_id, channel_id, amount, action, run_at
+        FROM scheduled_tips
+        WHERE id = $1 AND status = 'pending'",
+        id
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| {
+        Ok(ScheduledTip {
+            id: row.id,
+            author: UserId::new(row.author as u64),
+            spender: row.spender.map(|spender| UserId::new(spender as u64)),
+            guild_id: GuildId::new(row.guild_id as u64),
+            channel_id: ChannelId::new(row.channel_id as u64),
+            amount: Amount::from_sat(row.amount as u64),
+            action: serde_json::from_str(&row.action)?,
+            run_at: row.run_at,
+        })
+    })
+    .transpose()
+}
+
+/// Returns the scheduled tips that were claimed but never finished, because the bot stopped
+/// while carrying them out.
+pub async fn get_running_scheduled_tips(
+    conn: &mut PgConnection,
+) -> Result<Vec<ScheduledTip>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, author, spender, guild_id, channel_id, amount, action, run_at
+        FROM scheduled_tips
+        WHERE status = 'running'"
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(ScheduledTip {
+                id: row.id,
+                author: UserId::new(row.author as u64),
+                spender: row.spender.map(|spender| UserId::new(spender as u64)),
+                guild_id: GuildId::new(row.guild_id as u64),
+                channel_id: ChannelId::new(row.channel_id as u64),
+                amount: Amount::from_sat(row.amount as u64),
+                action: serde_json::from_str(&row.action)?,
+                run_at: row.run_at,
+            })
+        })
+        .collect()
+}
+
+/// Moves a scheduled tip from status `from` to `to`. Returns false when the tip did not have
+/// status `from` (anymore), in which case nothing changed.
+pub async fn update_scheduled_tip(
+    conn: &mut PgConnection,
+    id: i64,
+    from: ScheduledTipStatus,
+    to: ScheduledTipStatus,
+    last_error: Option<&str>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE scheduled_tips SET status = $3, last_error = COALESCE($4, last_error)
+        WHERE id = $1 AND status = $2",
+        id,
+        from.to_string(),
+        to.to_string(),
+        last_error
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Counts a failed attempt of a running scheduled tip and returns the number of attempts so
+/// far, or `None` when the tip is not running.
+pub async fn record_scheduled_tip_failure(
+    conn: &mut PgConnection,
+    id: i64,
+    error: &str,
+) -> Result<Option<i32>, Error> {
+    let row = sqlx::query!(
+        "UPDATE scheduled_tips SET attempts = attempts + 1, last_error = $2
+        WHERE id = $1 AND status = 'running'
+        RETURNING attempts",
+        id,
+        error
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| row.attempts))
+}
+
+/// Puts a running scheduled tip back in the queue, to be tried again at `run_at`.
+pub async fn retry_scheduled_tip(
+    conn: &mut PgConnection,
+    id: i64,
+    run_at: DateTime<Utc>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE scheduled_tips SET status = 'pending', run_at = $2
+        WHERE id = $1 AND status = 'running'",
+        id,
+        run_at
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn insert_airdrop(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    source: UserId,
+    amount: Amount,
+    currency_id: &Address,
+    action: &str,
+    schedule: &str,
+    next_run_at: DateTime<Utc>,
+    created_by: UserId,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO airdrops (guild_id, channel_id, source_account, amount, currency_id, action, schedule, next_run_at, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id",
+        guild_id.get() as i64,
+        channel_id.get() as i64,
+        source.get() as i64,
+        amount.as_sat() as i64,
+        currency_id.to_string(),
+        action,
+        schedule,
+        next_run_at,
+        created_by.get() as i64
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.id)
+}
+
+/// Returns the enabled airdrops, the ones that are due first. With `due_only`, only the ones
+/// that should run now.
+pub async fn get_airdrops(conn: &mut PgConnection, due_only: bool) -> Result<Vec<Airdrop>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, guild_id, channel_id, source_account, amount, currency_id, action, schedule, next_run_at
+        FROM airdrops
+        WHERE enabled AND (NOT $1 OR next_run_at <= now())
+        ORDER BY next_run_at",
+        due_only
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(Airdrop {
+                id: row.id,
+                guild_id: GuildId::new(row.guild_id as u64),
+                channel_id: ChannelId::new(row.channel_id as u64),
+                source: UserId::new(row.source_account as u64),
+                amount: Amount::from_sat(row.amount as u64),
+                currency_id: Address::from_str(&row.currency_id)?,
+                action: serde_json::from_str(&row.action)?,
+                schedule: row.schedule,
+                next_run_at: row.next_run_at,
+            })
+        })
+        .collect()
+}
+
+/// Moves an airdrop that is due at `due_at` to its next run, or disables it when it doesn't run
+/// again. Returns false when the airdrop was disabled or already moved on in the meantime.
+pub async fn advance_airdrop(
+    conn: &mut PgConnection,
+    id: i64,
+    due_at: DateTime<Utc>,
+    next_run_at: Option<DateTime<Utc>>,
+) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE airdrops SET next_run_at = COALESCE($3, next_run_at), enabled = $3 IS NOT NULL
+        WHERE id = $1 AND enabled AND next_run_at = $2",
+        id,
+        due_at,
+        next_run_at
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+/// Returns false when there was no enabled airdrop with this id.
+pub async fn disable_airdrop(conn: &mut PgConnection, id: i64) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        "UPDATE airdrops SET enabled = FALSE WHERE id = $1 AND enabled",
+        id
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+pub async fn insert_airdrop_run(
+    conn: &mut PgConnection,
+    airdrop_id: i64,
+    status: AirdropRunStatus,
+    error: Option<&str>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "INSERT INTO airdrop_runs (airdrop_id, status, error) VALUES ($1, $2, $3)",
+        airdrop_id,
+        status.to_string(),
+        error
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// The most recent runs of an airdrop, newest first.
+pub async fn get_airdrop_runs(
+    conn: &mut PgConnection,
+    airdrop_id: i64,
+    limit: i64,
+) -> Result<Vec<AirdropRun>, Error> {
+    let rows = sqlx::query!(
+        "SELECT status, error, created_at
+        FROM airdrop_runs
+        WHERE airdrop_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2",
+        airdrop_id,
+        limit
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| AirdropRun {
+            status: row.status,
+            error: row.error,
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+/// Returns `None` when the guild already has a pool with this name.
+pub async fn insert_pool(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+    name: &str,
+    spender_role: Option<RoleId>,
+    created_by: UserId,
+) -> Result<Option<Pool>, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO pools (guild_id, name, spender_role_id, created_by)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING id",
+        guild_id.get() as i64,
+        name,
+        spender_role.map(|role_id| role_id.get() as i64),
+        created_by.get() as i64
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| Pool {
+        id: row.id,
+        guild_id,
+        name: name.to_string(),
+        spender_role,
+    }))
+}
+
+/// Pool names are case insensitive.
+pub async fn get_pool(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+    name: &str,
+) -> Result<Option<Pool>, Error> {
+    let row = sqlx::query!(
+        "SELECT id, guild_id, name, spender_role_id
+        FROM pools
+        WHERE guild_id = $1 AND lower(name) = lower($2)",
+        guild_id.get() as i64,
+        name
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| Pool {
+        id: row.id,
+        guild_id: GuildId::new(row.guild_id as u64),
+        name: row.name,
+        spender_role: row.spender_role_id.map(|id| RoleId::new(id as u64)),
+    }))
+}
+
+pub async fn get_pool_by_id(conn: &mut PgConnection, id: i64) -> Result<Option<Pool>, Error> {
+    let row = sqlx::query!(
+        "SELECT id, guild_id, name, spender_role_id FROM pools WHERE id = $1",
+        id
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.map(|row| Pool {
+        id: row.id,
+        guild_id: GuildId::new(row.guild_id as u64),
+        name: row.name,
+        spender_role: row.spender_role_id.map(|id| RoleId::new(id as u64)),
+    }))
+}
+
+/// The pools of a guild with their balance in `currency_id`.
+pub async fn get_pools(
+    conn: &mut PgConnection,
+    guild_id: GuildId,
+    currency_id: &Address,
+) -> Result<Vec<(Pool, Amount)>, Error> {
+    let rows = sqlx::query!(
+        r#"SELECT pools.id, pools.guild_id, pools.name, pools.spender_role_id,
+            COALESCE(balances.balance, 0) AS "balance!"
+        FROM pools
+        LEFT JOIN balances ON balances.discord_id = -pools.id AND balances.currency_id = $2
+        WHERE pools.guild_id = $1
+        ORDER BY pools.name"#,
+        guild_id.get() as i64,
+        currency_id.to_string()
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (
+                Pool {
+                    id: row.id,
+                    guild_id: GuildId::new(row.guild_id as u64),
+                    name: row.name,
+                    spender_role: row.spender_role_id.map(|id| RoleId::new(id as u64)),
+                },
+                Amount::from_sat(row.balance as u64),
+            )
+        })
+        .collect())
+}
+
+pub async fn set_pool_spender_role(
+    conn: &mut PgConnection,
+    id: i64,
+    spender_role: Option<RoleId>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE pools SET spender_role_id = $2 WHERE id = $1",
+        id,
+        spender_role.map(|role_id| role_id.get() as i64)
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Stores a withdrawal that needs approval and returns its id.
+pub async fn insert_withdrawal_request(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    destination: &Address,
+    amount: Amount,
+    fee: Amount,
+    currency_id: &Address,
+) -> Result<i64, Error> {
+    let row = sqlx::query!(
+        "INSERT INTO withdrawal_requests (discord_id, destination, amount, fee, currency_id)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id",
+        user_id.get() as i64,
+        destination.to_string(),
+        amount.as_sat() as i64,
+        fee.as_sat() as i64,
+        currency_id.to_string()
+    )
+    .fetch_one(conn)
+    .await?;
+
+    Ok(row.id)
+}
+
+pub async fn get_pending_withdrawal_requests(
+    conn: &mut PgConnection,
+) -> Result<Vec<WithdrawalRequest>, Error> {
+    let rows = sqlx::query!(
+        "SELECT id, discord_id, destination, amount, fee, currency_id, created_at
+        FROM withdrawal_requests
+        WHERE status = 'pending'
+        ORDER BY created_at"
+    )
+    .fetch_all(conn)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(WithdrawalRequest {
+                id: row.id,
+                user_id: UserId::new(row.discord_id as u64),
+                destination: Address::from_str(&row.destination)?,
+                amount: Amount::from_sat(row.amount as u64),
+                fee: Amount::from_sat(row.fee as u64),
+                currency_id: Address::from_str(&row.currency_id)?,
+                created_at: row.created_at,
+            })
+        })
+        .collect()
+}
+
+/// Approves or rejects a pending withdrawal request and returns it, or `None` when there is no
+/// pending request with that id, e.g. because another admin just decided on it.
+pub async fn decide_withdrawal_request(
+    conn: &mut PgConnection,
+    id: i64,
+    status: WithdrawalRequestStatus,
+    decided_by: UserId,
+) -> Result<Option<WithdrawalRequest>, Error> {
+    let row = sqlx::query!(
+        "UPDATE withdrawal_requests SET status = $2, decided_by = $3
+        WHERE id = $1 AND status = 'pending'
+        RETURNING id, discord_id, destination, amount, fee, currency_id, created_at",
+        id,
+        status.to_string(),
+        decided_by.get() as i64
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    row.map(|row| {
+        Ok(WithdrawalRequest {
+            id: row.id,
+            user_id: UserId::new(row.discord_id as u64),
+            destination: Address::from_str(&row.destination)?,
+            amount: Amount::from_sat(row.amount as u64),
+            fee: Amount::from_sat(row.fee as u64),
+            currency_id: Address::from_str(&row.currency_id)?,
+            created_at: row.created_at,
+        })
+    })
+    .transpose()
+}
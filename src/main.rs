@@ -1,11 +1,18 @@
+pub mod activity;
+pub mod airdrop;
 pub mod commands;
 pub mod config;
 pub(crate) mod database;
+pub mod notifier;
+pub mod pool;
+pub mod price_alert;
 pub mod reactdrop;
+pub mod scheduler;
 pub mod util;
 pub mod wallet_listener;
 
 use crate::{
+    activity::RecentActivity,
     config::{Config, get_configuration},
     wallet_listener::TransactionProcessor,
 };
@@ -18,10 +25,14 @@ use secrecy::ExposeSecret;
 use sqlx::PgPool;
 use std::{
     collections::{HashMap, HashSet},
+    str::FromStr,
     sync::Arc,
     time::Duration,
 };
-use tokio::{pin, sync::RwLock};
+use tokio::{
+    pin,
+    sync::{Notify, RwLock},
+};
 use tokio_graceful_shutdown::{IntoSubsystem, SubsystemBuilder, SubsystemHandle, Toplevel};
 use tracing::{Level, debug, error, info, instrument, warn};
 use tracing_subscriber::{
@@ -46,9 +57,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let database = PgPool::connect_lazy(&config.database.connection_string())?;
     // sqlx::migrate!("./migrations").run(&database).await?;
 
+    // wakes the reactdrop subsystem up when a reactdrop is started or extended
+    let reactdrop_wakeup = Arc::new(Notify::new());
+
     let bot = Bot {
-        client: app(config, database.clone()).await?,
+        client: app(config.clone(), database.clone(), reactdrop_wakeup.clone()).await?,
         db: database,
+        config,
+        reactdrop_wakeup,
     };
 
     Toplevel::new(async |s: &mut SubsystemHandle| {
@@ -65,6 +81,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 struct Bot {
     client: serenity::Client,
     db: PgPool,
+    config: Config,
+    reactdrop_wakeup: Arc<Notify>,
 }
 
 impl IntoSubsystem<Box<dyn std::error::Error + Send + Sync>> for Bot {
@@ -76,8 +94,11 @@ impl IntoSubsystem<Box<dyn std::error::Error + Send + Sync>> for Bot {
         let http = client.http.clone();
 
         let reactdrop_service = reactdrop::Subsystem {
-            http,
+            http: http.clone(),
+            cache: client.cache.clone(),
             pool: self.db.clone(),
+            config: self.config.clone(),
+            wakeup: self.reactdrop_wakeup.clone(),
         };
 
         subsys.start(SubsystemBuilder::new(
@@ -85,6 +106,50 @@ impl IntoSubsystem<Box<dyn std::error::Error + Send + Sync>> for Bot {
             reactdrop_service.into_subsystem(),
         ));
 
+        let scheduler_service = scheduler::Subsystem {
+            http: http.clone(),
+            pool: self.db.clone(),
+            config: self.config.clone(),
+            reactdrop_wakeup: self.reactdrop_wakeup.clone(),
+        };
+
+        subsys.start(SubsystemBuilder::new(
+            "SchedulerService",
+            scheduler_service.into_subsystem(),
+        ));
+
+        let airdrop_service = airdrop::Subsystem {
+            http: http.clone(),
+            pool: self.db.clone(),
+            config: self.config.clone(),
+            reactdrop_wakeup: self.reactdrop_wakeup.clone(),
+        };
+
+        subsys.start(SubsystemBuilder::new(
+            "AirdropService",
+            airdrop_service.into_subsystem(),
+        ));
+
+        let price_alert_service = price_alert::Subsystem {
+            pool: self.db.clone(),
+            config: self.config,
+        };
+
+        subsys.start(SubsystemBuilder::new(
+            "PriceAlertService",
+            price_alert_service.into_subsystem(),
+        ));
+
+        let notifier_service = notifier::Subsystem {
+            http,
+            pool: self.db.clone(),
+        };
+
+        subsys.start(SubsystemBuilder::new(
+            "NotifierService",
+            notifier_service.into_subsystem(),
+        ));
+
         pin!(client);
 
         while !subsys.is_shutdown_requested() {
@@ -105,7 +170,11 @@ impl IntoSubsystem<Box<dyn std::error::Error + Send + Sync>> for Bot {
 }
 
 #[instrument(err)]
-async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error> {
+async fn app(
+    config: Config,
+    database: PgPool,
+    reactdrop_wakeup: Arc<Notify>,
+) -> Result<serenity::Client, Error> {
     let owners = config
         .application
         .owners
@@ -120,6 +189,9 @@ async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error
         commands: vec![
             admin::adminhelp(),
             admin::setwithdrawfee(),
+            admin::withdrawals(),
+            admin::approvewithdrawal(),
+            admin::rejectwithdrawal(),
             admin::rescanfromheight(),
             admin::depositenabled(),
             admin::withdrawenabled(),
@@ -128,6 +200,17 @@ async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error
             admin::maintenance(),
             admin::banned_balances(),
             admin::status(),
+            admin::auditlog(),
+            admin::grantrole(),
+            admin::revokerole(),
+            admin::credit(),
+            admin::debit(),
+            admin::airdroprole(),
+            admin::airdropreactdrop(),
+            admin::airdrops(),
+            admin::airdropruns(),
+            admin::removeairdrop(),
+            admin::admin(),
             misc::help(),
             misc::info(),
             misc::source(),
@@ -150,18 +233,22 @@ async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error
             wallet::balance(),
             wallet::withdraw(),
             wallet::donate_to_foundation(),
+            history::history(),
+            history::export(),
             tipping::tip(),
-            tipping::reactdrop(),
+            commands::reactdrop::reactdrop(),
+            commands::pool::pool(),
+            scheduled::scheduled(),
+            tipping::rain(),
+            stats::leaderboard(),
+            stats::stats(),
         ],
 
         command_check: Some(|ctx| {
-            let author = &ctx.author().id;
-            let owners = &ctx.data().owners;
-
             Box::pin(async move {
                 let maintenance_mode = { *ctx.data().tx_processor.maintenance.read().await };
 
-                if maintenance_mode && !owners.contains(author) {
+                if maintenance_mode && admin::admin_role(ctx, ctx.author().id).await?.is_none() {
                     ctx.send(CreateReply::default().content(
                             ":tools: The bot is in maintenance mode, we'll be right back :tools:",
                         ).ephemeral(true)
@@ -190,6 +277,9 @@ async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error
             })
         },
         on_error: |error| Box::pin(on_error(error)),
+        event_handler: |ctx, event, framework, data| {
+            Box::pin(event_handler(ctx, event, framework, data))
+        },
         owners,
 
         ..Default::default()
@@ -218,15 +308,13 @@ async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error
     let token = config_clone.application.discord.clone();
 
     let framework = poise::Framework::builder()
-        .setup(move |ctx, bot, _framework| {
-            let http = ctx.http.clone();
+        .setup(move |_ctx, bot, _framework| {
             let pool = database.clone();
             let deposits_enabled = Arc::new(RwLock::new(true));
             let deposits_enabled_clone = deposits_enabled.clone();
 
             Box::pin(async move {
                 let tx_proc = Arc::new(TransactionProcessor::new(
-                    http.clone(),
                     pool.clone(),
                     config_clone,
                     Arc::new(RwLock::new(false)),
@@ -287,6 +375,8 @@ async fn app(config: Config, database: PgPool) -> Result<serenity::Client, Error
                     tx_processor: tx_proc,
                     owners: owners_clone,
                     currency_names: HashMap::new(),
+                    activity: RecentActivity::default(),
+                    reactdrop_wakeup,
                 })
             })
         })
@@ -318,33 +408,37 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
                 .collect::<Vec<_>>()
                 .join(", ");
 
-            if let Err(e) = ChannelId::new(
-                ctx.data()
-                    .settings
-                    .application
-                    .discord_admin_thread_id
-                    .parse::<u64>()
-                    .unwrap(),
-            )
-            .send_message(
-                ctx.http(),
-                CreateMessage::new().content(format!(
-                    "
+            if let Err(e) = ctx
+                .data()
+                .admin_thread()
+                .send_message(
+                    ctx.http(),
+                    CreateMessage::new().content(format!(
+                        "
                 {s}, the following error occured:\n
                 - error message: {error}\n
                 - user that encounted error: {}\n
                 - command used: {}\n
                 - possible arguments used: {}",
-                    ctx.author().name,
-                    ctx.invoked_command_name(),
-                    ctx.invocation_string()
-                )),
-            )
-            .await
+                        ctx.author().name,
+                        ctx.invoked_command_name(),
+                        ctx.invocation_string()
+                    )),
+                )
+                .await
             {
                 error!("{}", e)
             }
         }
+        poise::FrameworkError::CommandCheckFailed {
+            error: None, ctx, ..
+        } => {
+            warn!(
+                user = %ctx.author().id,
+                command = ctx.invoked_command_name(),
+                "command check failed"
+            )
+        }
         poise::FrameworkError::ArgumentParse {
             error: _,
             input,
@@ -379,19 +473,39 @@ pub struct Data {
     tx_processor: Arc<TransactionProcessor>,
     owners: HashSet<UserId>,
     currency_names: HashMap<Address, String>,
+    activity: RecentActivity,
+    reactdrop_wakeup: Arc<Notify>,
 }
 
 impl Data {
     pub fn verus(&self) -> Result<VerusClient, Error> {
-        vrsc_rpc::client::Client::vrsc(
-            self.settings.application.testnet,
-            vrsc_rpc::Auth::UserPass(
-                format!("http://127.0.0.1:{}", self.settings.application.rpc_port),
-                self.settings.application.rpc_user.clone(),
-                self.settings.application.rpc_password.clone(),
-            ),
+        self.settings.application.verus_client()
+    }
+
+    /// The thread where admins get notified of errors and admin actions.
+    pub fn admin_thread(&self) -> ChannelId {
+        ChannelId::new(
+            self.settings
+                .application
+                .discord_admin_thread_id
+                .parse::<u64>()
+                .unwrap(),
         )
-        .map_err(|e| e.into())
+    }
+
+    /// Resolves a currency name (e.g. `VRSC` or `vETH`) or i-address to its currency id.
+    pub fn to_currency_id(&self, currency: &str) -> Result<Address, Error> {
+        if currency.eq_ignore_ascii_case("VRSC") {
+            return Ok(Address::from_str(VRSC_CURRENCY_ID)?);
+        }
+
+        if let Ok(address) = Address::from_str(currency) {
+            return Ok(address);
+        }
+
+        let client = self.verus()?;
+
+        Ok(client.get_currency(currency)?.currencyid)
     }
 
     pub fn to_currency_name(&self, address: &Address) -> Result<String, Error> {
@@ -408,6 +522,27 @@ impl Data {
     }
 }
 
+async fn event_handler(
+    _ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    _framework: poise::FrameworkContext<'_, Data, Error>,
+    data: &Data,
+) -> Result<(), Error> {
+    match event {
+        // keep track of who's chatting where, for `/rain` and role tips
+        serenity::FullEvent::Message { new_message } if !new_message.author.bot => {
+            data.activity.record(
+                new_message.guild_id,
+                new_message.channel_id,
+                new_message.author.id,
+            );
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
 fn log_setup() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let filter_layer = EnvFilter::try_from_default_env()
         .or_else(|_| EnvFilter::try_new("info"))
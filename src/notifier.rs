@@ -0,0 +1,183 @@
+use std::{fmt::Display, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, CreateMessage, Http, HttpError, UserId,
+};
+use sqlx::{PgConnection, PgPool};
+use tokio::time::sleep;
+use tokio_graceful_shutdown::{IntoSubsystem, SubsystemHandle};
+use tracing::{debug, error, trace, warn};
+
+use crate::{Error, database};
+
+/// Discord's error code for "Cannot send messages to this user", i.e. the user has DMs closed.
+const CANNOT_MESSAGE_USER: isize = 50007;
+const MAX_ATTEMPTS: i32 = 5;
+const BATCH_SIZE: i64 = 50;
+/// Time between two messages, to stay well clear of Discord's rate limits on opening DM channels.
+const PACING: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy)]
+pub enum Recipient {
+    /// Sent as a direct message.
+    User(UserId),
+    Channel(ChannelId),
+}
+
+#[derive(Debug)]
+pub enum OutboxStatus {
+    Pending,
+    Delivered,
+    /// Gave up after `MAX_ATTEMPTS`.
+    Failed,
+    /// The user doesn't accept direct messages, retrying won't help.
+    Undeliverable,
+}
+
+impl Display for OutboxStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Delivered => write!(f, "delivered"),
+            Self::Failed => write!(f, "failed"),
+            Self::Undeliverable => write!(f, "undeliverable"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct OutboxMessage {
+    pub id: i64,
+    pub recipient: Recipient,
+    pub payload: String,
+    pub attempts: i32,
+}
+
+/// Stores `message` in the outbox, from where the notifier subsystem delivers it.
+///
+/// Pass the transaction that moves the money: the message then only goes out once that
+/// transaction is committed, and is never lost when Discord happens to be unavailable.
+pub async fn queue(
+    conn: &mut PgConnection,
+    recipient: Recipient,
+    message: &CreateMessage,
+) -> Result<(), Error> {
+    let payload = serde_json::to_string(message)?;
+
+    database::insert_outbox_message(conn, recipient, &payload).await
+}
+
+/// Delivers the messages in the outbox, retrying failed deliveries with an increasing delay.
+pub struct Subsystem {
+    pub http: Arc<Http>,
+    pub pool: PgPool,
+}
+
+impl Subsystem {
+    async fn process_outbox(&self) -> Result<(), Error> {
+        let mut conn = self.pool.acquire().await?;
+        let messages = database::get_due_outbox_messages(&mut conn, BATCH_SIZE).await?;
+
+        if !messages.is_empty() {
+            debug!("{} messages in outbox", messages.len());
+        }
+
+        for message in messages {
+            let attempts = message.attempts + 1;
+
+            match self.deliver(&message).await {
+                Ok(()) => {
+                    trace!("delivered outbox message {}", message.id);
+
+                    database::update_outbox_message(
+                        &mut conn,
+                        message.id,
+                        OutboxStatus::Delivered,
+                        attempts,
+                        None,
+                        chrono::Utc::now(),
+                    )
+                    .await?;
+                }
+                Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                    if response.error.code == CANNOT_MESSAGE_USER =>
+                {
+                    debug!("{:?} does not accept direct messages", message.recipient);
+
+                    database::update_outbox_message(
+                        &mut conn,
+                        message.id,
+                        OutboxStatus::Undeliverable,
+                        attempts,
+                        Some(&response.error.message),
+                        chrono::Utc::now(),
+                    )
+                    .await?;
+                }
+                Err(e) => {
+                    warn!(
+                        "delivering outbox message {} failed (attempt {attempts}/{MAX_ATTEMPTS}): {e:?}",
+                        message.id
+                    );
+
+                    let status = if attempts >= MAX_ATTEMPTS {
+                        error!("giving up on outbox message {}", message.id);
+                        OutboxStatus::Failed
+                    } else {
+                        OutboxStatus::Pending
+                    };
+
+                    database::update_outbox_message(
+                        &mut conn,
+                        message.id,
+                        status,
+                        attempts,
+                        Some(&e.to_string()),
+                        next_attempt_at(attempts),
+                    )
+                    .await?;
+                }
+            }
+
+            sleep(PACING).await;
+        }
+
+        Ok(())
+    }
+
+    async fn deliver(&self, message: &OutboxMessage) -> Result<(), serenity::Error> {
+        let channel_id = match message.recipient {
+            Recipient::User(user_id) => user_id.create_dm_channel(&self.http).await?.id,
+            Recipient::Channel(channel_id) => channel_id,
+        };
+
+        // the payload is a serialized `CreateMessage`, which can be sent as is.
+        let payload: serde_json::Value = serde_json::from_str(&message.payload)?;
+        self.http.send_message(channel_id, vec![], &payload).await?;
+
+        Ok(())
+    }
+}
+
+// 30 seconds, 1 minute, 2 minutes, ...
+fn next_attempt_at(attempts: i32) -> DateTime<Utc> {
+    chrono::Utc::now() + chrono::Duration::seconds(15 << attempts.clamp(1, 10))
+}
+
+impl IntoSubsystem<Error> for Subsystem {
+    async fn run(self, subsys: &mut SubsystemHandle) -> Result<(), Error> {
+        while !subsys.is_shutdown_requested() {
+            if let Err(e) = self.process_outbox().await {
+                error!(?e, "Something went wrong when processing the outbox");
+            }
+
+            tokio::select! {
+                _ = sleep(Duration::from_secs(2)) => {}
+                _ = subsys.on_shutdown_requested() => {}
+            }
+        }
+
+        Ok(())
+    }
+}
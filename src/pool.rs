@@ -0,0 +1,60 @@
+use poise::serenity_prelude::{GuildId, RoleId, UserId};
+
+/// A shared account that doesn't belong to a Discord user, like the treasury of a server or the
+/// prize pool of an event.
+///
+/// A pool has a balance, deposit addresses and a history like any user, stored under an account
+/// id that is the negative of the pool id. Discord ids are always positive, so the account of a
+/// pool never clashes with the account of a user. That way tips, reactdrops and scheduled tips
+/// can be paid from a pool without knowing about pools.
+#[derive(Debug, Clone)]
+pub struct Pool {
+    pub id: i64,
+    pub guild_id: GuildId,
+    pub name: String,
+    /// Members with this role can spend from the pool. Treasurers always can.
+    pub spender_role: Option<RoleId>,
+}
+
+impl Pool {
+    /// The account that holds the balance of the pool.
+    pub fn account(&self) -> UserId {
+        UserId::new(-self.id as u64)
+    }
+}
+
+/// Returns the id of the pool when `account` belongs to a pool rather than a user.
+pub fn pool_id(account: UserId) -> Option<i64> {
+    let id = account.get() as i64;
+
+    (id < 0).then_some(-id)
+}
+
+/// How an account is shown in messages: a mention for users, the pool id for pools, as a
+/// mention of a pool account would show up as an unknown user.
+pub fn mention(account: UserId) -> String {
+    match pool_id(account) {
+        Some(id) => format!("pool #{id}"),
+        None => format!("<@{account}>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_accounts() {
+        let pool = Pool {
+            id: 42,
+            guild_id: GuildId::new(1),
+            name: String::from("treasury"),
+            spender_role: None,
+        };
+
+        assert_eq!(pool_id(pool.account()), Some(42));
+        assert_eq!(pool_id(UserId::new(1_234_567_890_123_456_789)), None);
+        assert_eq!(mention(pool.account()), "pool #42");
+        assert_eq!(mention(UserId::new(123)), "<@123>");
+    }
+}
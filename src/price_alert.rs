@@ -0,0 +1,123 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::CreateMessage;
+use sqlx::PgPool;
+use tokio::time::sleep;
+use tokio_graceful_shutdown::{IntoSubsystem, SubsystemHandle};
+use tracing::{debug, error, info};
+
+use crate::{
+    Error,
+    commands::chain,
+    config::Config,
+    database,
+    notifier::{self, Recipient},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Lets the users who turned price alerts on know when the price of VRSC moves, see
+/// `price_alert_percent` in the config.
+pub struct Subsystem {
+    pub pool: PgPool,
+    pub config: Config,
+}
+
+impl Subsystem {
+    /// Checks the price against `last_price`, the price of the last alert, and queues an alert
+    /// for every subscriber when it moved by at least `threshold` percent.
+    ///
+    /// Returns the price to compare the next check with.
+    async fn check_price(&self, last_price: Option<f64>, threshold: f64) -> Result<f64, Error> {
+        let ticker = chain::vrsc_ticker().await?;
+        let price = ticker
+            .quotes
+            .get("USD")
+            .map(|quote| quote.price)
+            .ok_or("the ticker has no USD quote")?;
+
+        // the first price after a start is only remembered, there is nothing to compare it with
+        let Some(last_price) = last_price else {
+            return Ok(price);
+        };
+
+        let Some(change) = percent_change(last_price, price) else {
+            return Ok(price);
+        };
+
+        if change.abs() < threshold {
+            debug!("VRSC price ${price:.4} is {change:+.2}% from ${last_price:.4}");
+
+            return Ok(last_price);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let subscribers = database::get_price_alert_subscribers(&mut tx).await?;
+
+        let emoji = if change.is_sign_positive() {
+            ":chart_with_upwards_trend:"
+        } else {
+            ":chart_with_downwards_trend:"
+        };
+        let message = CreateMessage::new().content(format!(
+            "{emoji} The price of VRSC moved {change:+.2}% to ${price:.4} since the last alert \
+            (${last_price:.4}).\n\
+            You get this message because price alerts are on, turn them off with `/notifications`."
+        ));
+
+        for user_id in &subscribers {
+            notifier::queue(&mut tx, Recipient::User(*user_id), &message).await?;
+        }
+
+        tx.commit().await?;
+
+        info!(
+            "VRSC price moved {change:+.2}% to ${price:.4}, alerted {} users",
+            subscribers.len()
+        );
+
+        Ok(price)
+    }
+}
+
+/// The change from `from` to `to` in percent, if there is a price to compare with.
+fn percent_change(from: f64, to: f64) -> Option<f64> {
+    (from > 0.0).then(|| (to - from) / from * 100.0)
+}
+
+impl IntoSubsystem<Error> for Subsystem {
+    async fn run(self, subsys: &mut SubsystemHandle) -> Result<(), Error> {
+        let Some(threshold) = self.config.application.price_alert_percent else {
+            return Ok(());
+        };
+
+        let mut last_price = None;
+
+        while !subsys.is_shutdown_requested() {
+            match self.check_price(last_price, threshold).await {
+                Ok(price) => last_price = Some(price),
+                Err(e) => error!(?e, "Something went wrong when checking the price"),
+            }
+
+            tokio::select! {
+                _ = sleep(POLL_INTERVAL) => {}
+                _ = subsys.on_shutdown_requested() => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_changes() {
+        assert_eq!(percent_change(2.0, 2.5), Some(25.0));
+        assert_eq!(percent_change(2.0, 1.5), Some(-25.0));
+        assert_eq!(percent_change(2.0, 2.0), Some(0.0));
+        assert_eq!(percent_change(0.0, 1.0), None);
+    }
+}
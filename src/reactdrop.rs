@@ -1,24 +1,66 @@
-use std::{fmt::Display, str::FromStr, sync::Arc, time::Duration};
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, stream};
 
 use poise::serenity_prelude::{
-    ArgumentConvert, ChannelId, CreateMessage, EditMessage, Http, Message, MessageId, ReactionType,
-    UserId,
-};
-use sqlx::{
-    PgPool,
-    types::chrono::{self, DateTime, Utc},
+    Cache, ChannelId, CreateAllowedMentions, CreateMessage, EditMessage, GuildId, Http, MessageId,
+    ReactionType, RoleId, Timestamp, User, UserId,
 };
-use tokio::time::sleep;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tokio::{sync::Notify, time::sleep};
 use tokio_graceful_shutdown::{IntoSubsystem, SubsystemHandle};
-use tracing::{debug, error, info, trace};
-use vrsc::Amount;
+use tracing::{debug, error, info, trace, warn};
+use vrsc::{Address, Amount};
+use vrsc_rpc::client::{Client as VerusClient, RpcApi};
+
+use crate::{
+    Error, VRSC_CURRENCY_ID,
+    commands::{
+        self,
+        tipping::{Funding, Recipients},
+    },
+    config::Config,
+    database,
+    notifier::{self, Recipient},
+    pool, scheduler,
+};
 
-use crate::{Error, commands, database};
+/// How many times ending a reactdrop is tried before it is given up on.
+const MAX_ATTEMPTS: i32 = 8;
+/// The wait before the first retry, doubled after every failed attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// The longest the subsystem sleeps, even when no reactdrop is due. Catches reactdrops that were
+/// inserted without waking the subsystem up.
+const MAX_WAIT: Duration = Duration::from_secs(5 * 60);
+/// The average time between two Verus blocks.
+const BLOCK_TIME_SECS: u64 = 60;
+/// Extra blocks before the winners of a giveaway are drawn, on top of the blocks that are
+/// expected until it ends.
+const DRAW_MARGIN_BLOCKS: u64 = 5;
+/// How often to check whether the block the winners of a giveaway are drawn with was mined.
+const DRAW_POLL: Duration = Duration::from_secs(30);
+/// How many participants that are not cached are fetched at the same time, to stay clear of the
+/// rate limits of Discord.
+const MEMBER_FETCHES: usize = 4;
+/// Replaces the countdown of a giveaway whose entries are closed.
+const ENTRIES_CLOSED: &str = "Entries are closed, the winners will be drawn soon";
 
 #[derive(Debug)]
 pub enum ReactdropState {
     Pending,
     Processed,
+    /// Cancelled by its author or an admin before it ended.
+    Cancelled,
+    /// Ending the reactdrop kept failing and there was no escrow to give back.
+    Failed,
+    /// Ending the reactdrop kept failing and the escrow went back to the author.
+    Refunded,
+    /// Nobody eligible joined, the escrow went back to the author.
+    NoParticipants,
 }
 
 impl Display for ReactdropState {
@@ -26,45 +68,453 @@ impl Display for ReactdropState {
         match self {
             Self::Pending => write!(f, "pending"),
             Self::Processed => write!(f, "processed"),
+            Self::Cancelled => write!(f, "cancelled"),
+            Self::Failed => write!(f, "failed"),
+            Self::Refunded => write!(f, "refunded"),
+            Self::NoParticipants => write!(f, "no_participants"),
         }
     }
 }
 
-impl From<String> for ReactdropState {
-    fn from(value: String) -> Self {
+impl TryFrom<String> for ReactdropState {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
         match value.as_ref() {
-            "pending" => ReactdropState::Pending,
-            "processed" => ReactdropState::Processed,
-            _ => unreachable!(),
+            "pending" => Ok(ReactdropState::Pending),
+            "processed" => Ok(ReactdropState::Processed),
+            "cancelled" => Ok(ReactdropState::Cancelled),
+            "failed" => Ok(ReactdropState::Failed),
+            "refunded" => Ok(ReactdropState::Refunded),
+            "no_participants" => Ok(ReactdropState::NoParticipants),
+            other => Err(format!("unknown reactdrop state: {other}").into()),
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Reactdrop {
+    pub id: i64,
     pub author: UserId,
+    /// The member that paid for the reactdrop from a pool, the pool is the author then.
+    pub spender: Option<UserId>,
     pub status: ReactdropState,
     pub emoji: String,
     pub tip_amount: Amount,
     pub channel_id: ChannelId,
     pub message_id: MessageId,
+    pub guild_id: Option<GuildId>,
     pub finish_time: DateTime<Utc>,
+    /// When set, this reactdrop is a giveaway: only this many participants, drawn at random,
+    /// get a share.
+    pub winners: Option<u32>,
+    /// The amount was taken from the author when the reactdrop started.
+    pub escrowed: bool,
+    pub requirements: Requirements,
+    /// The block whose hash seeds the draw of a giveaway, committed to when it started.
+    pub draw_height: Option<u64>,
+    /// The participants of a giveaway, stored when it ended.
+    pub entrants: Option<Recipients>,
+    /// When ending the reactdrop failed, when to try again.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Optional constraints on who can participate in a reactdrop, to keep alt accounts out.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Requirements {
+    pub role: Option<RoleId>,
+    pub min_account_age_days: Option<u32>,
+    pub min_membership_days: Option<u32>,
+    /// At most this many participants, picked at random, get a share.
+    pub max_participants: Option<u32>,
+}
+
+impl Requirements {
+    /// Whether the guild member data is needed to check these requirements.
+    pub fn needs_member(&self) -> bool {
+        self.role.is_some() || self.min_membership_days.is_some()
+    }
+
+    /// Checks a participant against the requirements. `joined_at` and `roles` come from the
+    /// participant's guild membership, if any.
+    pub fn is_met(
+        &self,
+        account_created: DateTime<Utc>,
+        joined_at: Option<DateTime<Utc>>,
+        roles: &[RoleId],
+        now: DateTime<Utc>,
+    ) -> bool {
+        let old_enough = |since: DateTime<Utc>, days: u32| {
+            now.signed_duration_since(since) >= chrono::Duration::days(days as i64)
+        };
+
+        if self.role.is_some_and(|role| !roles.contains(&role)) {
+            return false;
+        }
+
+        if self
+            .min_account_age_days
+            .is_some_and(|days| !old_enough(account_created, days))
+        {
+            return false;
+        }
+
+        match (self.min_membership_days, joined_at) {
+            (Some(days), Some(joined_at)) => old_enough(joined_at, days),
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+
+    /// A short description of the requirements for the reactdrop message, if there are any.
+    pub fn describe(&self) -> Option<String> {
+        let mut requirements = vec![];
+
+        if let Some(role) = self.role {
+            requirements.push(format!("have the <@&{role}> role"));
+        }
+        if let Some(days) = self.min_account_age_days {
+            requirements.push(format!(
+                "have an account that is at least {days} day(s) old"
+            ));
+        }
+        if let Some(days) = self.min_membership_days {
+            requirements.push(format!(
+                "be a member of this server for at least {days} day(s)"
+            ));
+        }
+
+        let mut description = (!requirements.is_empty())
+            .then(|| format!("To participate you need to {}", requirements.join(" and ")));
+
+        if let Some(max) = self.max_participants {
+            let cap = format!("At most {max} participant(s), picked at random, get a share");
+            description = Some(match description {
+                Some(description) => format!("{description}\n{cap}"),
+                None => cap,
+            });
+        }
+
+        description
+    }
+}
+
+impl Reactdrop {
+    fn funding(&self) -> Funding {
+        if self.escrowed {
+            Funding::Escrow
+        } else {
+            Funding::Balance
+        }
+    }
+
+    /// When the reactdrop is to be ended: when it finishes, or later when ending it failed.
+    fn due_at(&self) -> DateTime<Utc> {
+        self.next_attempt_at
+            .map_or(self.finish_time, |next_attempt_at| {
+                next_attempt_at.max(self.finish_time)
+            })
+    }
+}
+
+/// When a participant joined the guild, and their roles there.
+type MemberInfo = (Option<DateTime<Utc>>, Vec<RoleId>);
+
+fn timestamp_to_utc(timestamp: Timestamp) -> DateTime<Utc> {
+    DateTime::from_timestamp(timestamp.unix_timestamp(), 0).unwrap_or_default()
+}
+
+/// The line at the end of a reactdrop message that tells when it ends. Discord shows the
+/// timestamp relative to the current time, so the message never needs to be edited to count down.
+pub fn countdown(finish_time: DateTime<Utc>) -> String {
+    format!("Ends <t:{}:R>", finish_time.timestamp())
+}
+
+fn countdown_start(content: &str) -> Option<usize> {
+    // reactdrops started before the relative timestamp was used have a "Time remaining" line
+    content
+        .rfind("Ends <t:")
+        .or_else(|| content.rfind(ENTRIES_CLOSED))
+        .or_else(|| content.find("Time remaining: "))
+}
+
+/// The height of the block whose hash seeds the draw of a giveaway that lasts `duration_secs`.
+/// Blocks don't come at a fixed pace, so a tenth more blocks than expected and a margin are
+/// added: the block is all but certain to be mined after the giveaway ended.
+pub fn draw_height(current_height: u64, duration_secs: i64) -> u64 {
+    let expected = (duration_secs.max(0) as u64).div_ceil(BLOCK_TIME_SECS);
+
+    current_height + expected + expected / 10 + DRAW_MARGIN_BLOCKS
+}
+
+/// The block the winners are drawn with once the entries of a giveaway are closed. That is the
+/// committed block, unless it was already mined, e.g. because the giveaway was extended: its
+/// hash would be known to the participants then, so a block in the future is taken instead.
+fn closing_draw_height(committed: Option<u64>, current_height: u64) -> u64 {
+    committed
+        .filter(|height| *height > current_height)
+        .unwrap_or(current_height + DRAW_MARGIN_BLOCKS)
+}
+
+/// Shows the new end of an extended reactdrop in its message.
+pub async fn update_countdown(http: &Http, reactdrop: &Reactdrop) -> Result<(), Error> {
+    let mut message = reactdrop
+        .channel_id
+        .message(http, reactdrop.message_id)
+        .await?;
+
+    let split = countdown_start(&message.content).ok_or("reactdrop message has no countdown")?;
+    let content = format!(
+        "{}{}",
+        &message.content[..split],
+        countdown(reactdrop.finish_time)
+    );
+
+    message
+        .edit(http, EditMessage::new().content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// Replaces the countdown in the message of a reactdrop with `status` and removes the reactions,
+/// so it is clear that the reactdrop is over.
+pub async fn close_message(http: &Http, reactdrop: &Reactdrop, status: &str) -> Result<(), Error> {
+    let mut message = reactdrop
+        .channel_id
+        .message(http, reactdrop.message_id)
+        .await?;
+
+    let content = match countdown_start(&message.content) {
+        Some(split) => format!("{}{status}", &message.content[..split]),
+        None => format!("{}\n\n{status}", message.content),
+    };
+
+    message
+        .edit(http, EditMessage::new().content(content))
+        .await?;
+    message
+        .delete_reaction_emoji(http, ReactionType::from_str(&reactdrop.emoji)?)
+        .await?;
+
+    Ok(())
+}
+
+/// Gives the escrowed amount of a reactdrop that won't be paid out back to its author. Does
+/// nothing for reactdrops that were not escrowed.
+pub async fn refund(conn: &mut PgConnection, reactdrop: &Reactdrop) -> Result<(), Error> {
+    if reactdrop.escrowed {
+        database::increase_balance(
+            conn,
+            &reactdrop.author,
+            reactdrop.tip_amount,
+            &Address::from_str(VRSC_CURRENCY_ID)?,
+        )
+        .await?;
+
+        info!(
+            "refunded {} to {} for reactdrop {}",
+            reactdrop.tip_amount, reactdrop.author, reactdrop.message_id
+        );
+    }
+
+    Ok(())
 }
 
+/// Ends reactdrops when they are due. Sleeps until the next reactdrop ends, or until it is woken
+/// up through `wakeup` because a reactdrop was started or extended.
 pub struct Subsystem {
     pub http: Arc<Http>,
+    /// Participants are looked up in the cache first when the requirements are checked.
+    pub cache: Arc<Cache>,
     pub pool: PgPool,
+    pub config: Config,
+    pub wakeup: Arc<Notify>,
 }
 
 impl Subsystem {
-    pub async fn check_running_reactdrops(&self) -> Result<(), Error> {
+    /// Closes the entries of a giveaway that ended: its participants are stored, so nobody can
+    /// join once the hash of the block the winners are drawn with is known.
+    async fn close_entries(
+        &self,
+        reactdrop: &Reactdrop,
+        entrants: &Recipients,
+        current_height: u64,
+    ) -> Result<(), Error> {
+        let draw_height = closing_draw_height(reactdrop.draw_height, current_height);
+
         let mut tx = self.pool.begin().await?;
-        let pending_reactdrops = database::get_pending_reactdrops(&mut tx).await?;
 
-        if pending_reactdrops.is_empty() {
+        if !database::close_reactdrop_entries(&mut tx, reactdrop.id, entrants, draw_height).await? {
+            warn!("reactdrop {} was cancelled while ending", reactdrop.id);
+
             return Ok(());
         }
 
+        let expected = chrono::Utc::now()
+            + chrono::Duration::seconds(((draw_height - current_height) * BLOCK_TIME_SECS) as i64);
+        notifier::queue(
+            &mut tx,
+            Recipient::Channel(reactdrop.channel_id),
+            &CreateMessage::new().content(format!(
+                ":lock: Entries are closed with {} participant(s). The winners are drawn with \
+                the hash of block {draw_height}, expected <t:{}:R>.",
+                entrants.users.len(),
+                expected.timestamp()
+            )),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        debug!(
+            "closed the entries of reactdrop {}, drawing with block {draw_height}",
+            reactdrop.id
+        );
+
+        if let Err(e) = close_message(&self.http, reactdrop, ENTRIES_CLOSED).await {
+            warn!("could not close reactdrop {}: {e:?}", reactdrop.id);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the recipients with the winners of the giveaway. The seed is the hash of the
+    /// block that was committed to before the entries were closed, and is published so anyone
+    /// can verify the draw.
+    ///
+    /// The winners split the whole amount, also when there were fewer participants than
+    /// `winners`: then they all win, and nothing is left in escrow.
+    async fn draw(
+        &self,
+        conn: &mut PgConnection,
+        client: &VerusClient,
+        reactdrop: &Reactdrop,
+        recipients: &mut Recipients,
+        winners: u32,
+        height: u64,
+    ) -> Result<(), Error> {
+        let block_hash = client.get_block_hash(height)?.to_string();
+        let seed = seed_from_block_hash(&block_hash).ok_or("block hash is not 32 bytes of hex")?;
+
+        let participants = recipients.users.len();
+        recipients.users = draw_winners(
+            std::mem::take(&mut recipients.users),
+            winners as usize,
+            seed,
+        );
+        debug!("giveaway winners: {:?}", recipients.users);
+
+        database::set_reactdrop_draw_seed(
+            conn,
+            reactdrop.channel_id.get() as i64,
+            reactdrop.message_id.get() as i64,
+            &format!("{height}:{block_hash}"),
+        )
+        .await?;
+
+        notifier::queue(
+            conn,
+            Recipient::Channel(reactdrop.channel_id),
+            &CreateMessage::new().content(format!(
+                ":game_die: {} winner(s) drawn from {participants} participant(s), they share {}.\n\
+                Seed: hash of block {height}, `{block_hash}`. To verify the draw, sort the \
+                participants by user id and seed ChaCha20 with the bytes of that hash. Then for \
+                every winner `i`, counting from 0, take the next u64 `r` and swap participant `i` \
+                with participant `i + (r * (participants - i)) >> 64`: the first participants win.",
+                recipients.users.len(),
+                reactdrop.tip_amount,
+            )),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Keeps the participants that meet the requirements of the reactdrop.
+    async fn meeting_requirements(&self, reactdrop: &Reactdrop, users: Vec<User>) -> Vec<User> {
+        let requirements = &reactdrop.requirements;
+        let now = chrono::Utc::now();
+
+        let members = match reactdrop.guild_id {
+            Some(guild_id) if requirements.needs_member() => {
+                Some(self.members(guild_id, &users).await)
+            }
+            _ => None,
+        };
+
+        users
+            .into_iter()
+            .filter(|user| {
+                let account_created = timestamp_to_utc(user.id.created_at());
+
+                let (joined_at, roles) = match &members {
+                    Some(members) => match members.get(&user.id) {
+                        Some((joined_at, roles)) => (*joined_at, roles.as_slice()),
+                        // most likely left the server in the meantime
+                        None => return false,
+                    },
+                    None => (None, [].as_slice()),
+                };
+
+                let met = requirements.is_met(account_created, joined_at, roles, now);
+                if !met {
+                    trace!("{} does not meet the requirements", user.id);
+                }
+
+                met
+            })
+            .collect()
+    }
+
+    /// The join date and roles of the participants that are members of the guild. Members are
+    /// taken from the cache, the ones that are not cached are fetched `MEMBER_FETCHES` at a time.
+    async fn members(&self, guild_id: GuildId, users: &[User]) -> HashMap<UserId, MemberInfo> {
+        let mut members = HashMap::new();
+        let mut uncached = vec![];
+
+        if let Some(guild) = self.cache.guild(guild_id) {
+            for user in users {
+                match guild.members.get(&user.id) {
+                    Some(member) => {
+                        members.insert(
+                            user.id,
+                            (member.joined_at.map(timestamp_to_utc), member.roles.clone()),
+                        );
+                    }
+                    None => uncached.push(user.id),
+                }
+            }
+        } else {
+            uncached.extend(users.iter().map(|user| user.id));
+        }
+
+        let fetched = stream::iter(uncached)
+            .map(|user_id| async move {
+                guild_id
+                    .member(&self.http, user_id)
+                    .await
+                    .inspect_err(|e| debug!("could not get member {user_id}: {e:?}"))
+            })
+            .buffer_unordered(MEMBER_FETCHES)
+            .collect::<Vec<_>>()
+            .await;
+
+        for member in fetched.into_iter().flatten() {
+            members.insert(
+                member.user.id,
+                (member.joined_at.map(timestamp_to_utc), member.roles),
+            );
+        }
+
+        members
+    }
+
+    /// Ends the reactdrops that are due and returns how long to wait before the next one is.
+    pub async fn check_running_reactdrops(&self) -> Result<Duration, Error> {
+        let mut conn = self.pool.acquire().await?;
+        let pending_reactdrops = database::get_pending_reactdrops(&mut conn).await?;
+
         let now = chrono::Utc::now();
         trace!(
             "number of pending reactdrops.{} at.{}",
@@ -72,137 +522,380 @@ impl Subsystem {
             now
         );
 
+        let mut wait = MAX_WAIT;
+
+        // every reactdrop is handled on its own, so one that fails doesn't hold up the others.
         for reactdrop in pending_reactdrops {
-            let mut message: Message = ArgumentConvert::convert(
-                &self.http,
-                None,
-                Some(reactdrop.channel_id),
-                reactdrop.message_id.to_string().as_ref(),
-            )
-            .await?;
+            let due_at = reactdrop.due_at();
+            if due_at > now {
+                let until_due = (due_at - now).to_std().unwrap_or_default();
+                wait = wait.min(until_due);
+
+                continue;
+            }
+
+            match self.end_reactdrop(&reactdrop).await {
+                Ok(Some(until_draw)) => wait = wait.min(until_draw),
+                Ok(None) => {}
+                Err(e) => {
+                    error!("ending reactdrop {} failed: {e:?}", reactdrop.id);
+                    wait = wait.min(RETRY_DELAY);
 
-            let diff = reactdrop.finish_time.signed_duration_since(now);
-            let diff_fmt = || -> String {
-                match diff.num_seconds() {
-                    t @ 0..=3600 => format!("{} minute(s)", t / 60),
-                    t => {
-                        format!("{} hour(s) and {} minute(s)", t / (60 * 60), (t / 60) % 60)
+                    if let Err(e) = self.record_failure(&reactdrop, e).await {
+                        error!(
+                            "could not record the failure of reactdrop {}: {e:?}",
+                            reactdrop.id
+                        );
                     }
                 }
-            };
-            debug!("{diff:?}");
+            }
+        }
 
-            let content: &str = message.content.as_ref();
-            let split = content.find("Time remaining: ").unwrap();
-            let new_content = format!("{}Time remaining: {}", &content[..split], diff_fmt());
+        Ok(wait)
+    }
 
-            message
-                .edit(&self.http, EditMessage::new().content(new_content))
+    /// Collects the users that reacted to a reactdrop and can get a share of it.
+    async fn participants(&self, reactdrop: &Reactdrop) -> Result<Recipients, Error> {
+        let message = reactdrop
+            .channel_id
+            .message(&self.http, reactdrop.message_id)
+            .await?;
+
+        let mut last_user = None;
+        let mut reaction_users = vec![];
+
+        loop {
+            let users = message
+                .reaction_users(
+                    &self.http,
+                    ReactionType::from_str(&reactdrop.emoji)?,
+                    Some(50),
+                    last_user,
+                )
                 .await?;
 
-            if reactdrop.finish_time <= now {
-                let mut last_user = None;
-                let mut reaction_users = vec![];
-
-                while let Ok(users) = message
-                    .reaction_users(
-                        &self.http,
-                        ReactionType::from_str(&reactdrop.emoji)?,
-                        Some(50),
-                        last_user,
-                    )
-                    .await
-                {
-                    debug!("appending {} users", users.len());
-                    reaction_users.extend(users.clone());
+            debug!("appending {} users", users.len());
+            debug!("{users:?}");
 
-                    debug!("{users:?}");
+            last_user = users.last().map(|user| user.id);
+            reaction_users.extend(users);
 
-                    last_user = users.last().map(|user| user.id);
-                    if last_user.is_none() {
-                        break;
-                    }
-                }
+            if last_user.is_none() {
+                break;
+            }
+        }
 
-                debug!(
-                    "retrieved {} users who reacted on reactdrop tip\n{:#?}",
-                    reaction_users.len(),
-                    reaction_users
-                );
-
-                let reaction_users = reaction_users
-                    .iter()
-                    .filter(|user| !user.bot)
-                    .map(|u| u.id)
-                    .collect::<Vec<_>>();
-
-                if reaction_users.is_empty() {
-                    trace!("no users to tip, abort");
-                } else {
-                    trace!("tipping {} users in reactdrop", reaction_users.len());
-
-                    if let Err(e) = commands::tipping::tip_multiple_users(
-                        &mut tx,
-                        reactdrop.author,
-                        &self.http,
-                        &reactdrop.channel_id,
-                        reaction_users,
-                        &reactdrop.tip_amount,
-                        "reactdrop",
-                    )
-                    .await
-                    {
-                        error!("{e:?}");
-
-                        reactdrop
-                            .channel_id
-                            .send_message(
-                                &self.http,
-                                CreateMessage::new().content(format!(
-                                    "<@{}> didn't have enough funds, reactdrop failed",
-                                    &message.author.id,
-                                )),
-                            )
+        debug!(
+            "retrieved {} users who reacted on reactdrop tip\n{:#?}",
+            reaction_users.len(),
+            reaction_users
+        );
+
+        let participants = reaction_users.len();
+        let reaction_users = self.meeting_requirements(reactdrop, reaction_users).await;
+
+        let mut conn = self.pool.acquire().await?;
+        let mut recipients = commands::tipping::eligible_recipients(
+            &mut conn,
+            reactdrop.author,
+            reactdrop.spender,
+            &reaction_users,
+        )
+        .await?;
+        // count the users that didn't meet the requirements as excluded too
+        recipients.excluded = participants - recipients.users.len();
+
+        Ok(recipients)
+    }
+
+    /// Pays out a reactdrop that has ended, in a transaction of its own. The entries of a
+    /// giveaway are closed first, and its winners drawn once the block they are drawn with is
+    /// mined: until then, the time to wait before checking again is returned.
+    async fn end_reactdrop(&self, reactdrop: &Reactdrop) -> Result<Option<Duration>, Error> {
+        let mut recipients = match &reactdrop.entrants {
+            Some(entrants) => entrants.clone(),
+            None => self.participants(reactdrop).await?,
+        };
+
+        let mut draw = None;
+        match reactdrop.winners {
+            Some(winners) if !recipients.users.is_empty() => {
+                let client = self.config.application.verus_client()?;
+                let current_height = client.get_blockchain_info()?.blocks;
+
+                let draw_height = match (&reactdrop.entrants, reactdrop.draw_height) {
+                    (Some(_), Some(draw_height)) => draw_height,
+                    _ => {
+                        self.close_entries(reactdrop, &recipients, current_height)
                             .await?;
+
+                        return Ok(Some(DRAW_POLL));
                     }
+                };
+
+                if current_height < draw_height {
+                    trace!(
+                        "reactdrop {} waits for block {draw_height}, at {current_height}",
+                        reactdrop.id
+                    );
+
+                    return Ok(Some(DRAW_POLL));
                 }
 
-                reactdrop
-                    .channel_id
-                    .delete_reaction_emoji(
-                        &self.http,
-                        message,
-                        ReactionType::from_str(&reactdrop.emoji)?,
-                    )
-                    .await?;
+                // the cap on the participants caps the winners of a giveaway, so the draw stays
+                // reproducible
+                let winners = reactdrop
+                    .requirements
+                    .max_participants
+                    .map_or(winners, |max| winners.min(max));
+                draw = Some((client, winners, draw_height));
+            }
+            _ => {
+                if let Some(max) = reactdrop.requirements.max_participants {
+                    recipients.sample(max as usize);
+                }
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
 
-                database::update_reactdrop(
+        let state = if recipients.users.is_empty() {
+            trace!("no users to tip, abort");
+
+            refund(&mut tx, reactdrop).await?;
+
+            let content = if reactdrop.escrowed {
+                format!(
+                    "Nobody joined the reactdrop, {} was returned to {}",
+                    reactdrop.tip_amount,
+                    pool::mention(reactdrop.author)
+                )
+            } else {
+                "Nobody joined the reactdrop".to_string()
+            };
+
+            notifier::queue(
+                &mut tx,
+                Recipient::Channel(reactdrop.channel_id),
+                &CreateMessage::new().content(content),
+            )
+            .await?;
+
+            ReactdropState::NoParticipants
+        } else {
+            if let Some((client, winners, height)) = &draw {
+                self.draw(
                     &mut tx,
-                    reactdrop.channel_id.get() as i64,
-                    reactdrop.message_id.get() as i64,
-                    ReactdropState::Processed,
+                    client,
+                    reactdrop,
+                    &mut recipients,
+                    *winners,
+                    *height,
                 )
                 .await?;
-
-                info!("processed reactdrop: {reactdrop:#?}");
             }
+
+            trace!("tipping {} users in reactdrop", recipients.users.len());
+
+            commands::tipping::tip_multiple_users(
+                &mut tx,
+                reactdrop.author,
+                &reactdrop.channel_id,
+                reactdrop.guild_id,
+                recipients,
+                &reactdrop.tip_amount,
+                "reactdrop",
+                None,
+                reactdrop.funding(),
+            )
+            .await?;
+
+            ReactdropState::Processed
+        };
+
+        if !database::update_reactdrop(
+            &mut tx,
+            reactdrop.channel_id.get() as i64,
+            reactdrop.message_id.get() as i64,
+            state,
+        )
+        .await?
+        {
+            // dropping the transaction rolls back the payout
+            warn!("reactdrop {} was cancelled while ending", reactdrop.id);
+
+            return Ok(None);
         }
 
         tx.commit().await?;
 
+        info!("processed reactdrop: {reactdrop:#?}");
+
+        if let Err(e) = close_message(&self.http, reactdrop, "This reactdrop has ended").await {
+            warn!("could not close reactdrop {}: {e:?}", reactdrop.id);
+        }
+
+        Ok(None)
+    }
+
+    /// Counts a failed attempt to end a reactdrop. After `MAX_ATTEMPTS` the reactdrop is given
+    /// up on: its escrow goes back to the author and the admins are told what went wrong.
+    async fn record_failure(&self, reactdrop: &Reactdrop, error: Error) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(attempts) =
+            database::record_reactdrop_failure(&mut tx, reactdrop.id, &error.to_string()).await?
+        else {
+            // not pending anymore
+            return Ok(());
+        };
+
+        if attempts < MAX_ATTEMPTS {
+            let next_attempt_at = chrono::Utc::now()
+                + chrono::Duration::from_std(scheduler::backoff(RETRY_DELAY, attempts))?;
+            database::retry_reactdrop(&mut tx, reactdrop.id, next_attempt_at).await?;
+
+            tx.commit().await?;
+
+            debug!(
+                "reactdrop {} will be tried again at {next_attempt_at}",
+                reactdrop.id
+            );
+
+            return Ok(());
+        }
+
+        error!(
+            "giving up on reactdrop {} after {attempts} attempts",
+            reactdrop.id
+        );
+
+        let (state, content) = if reactdrop.escrowed {
+            refund(&mut tx, reactdrop).await?;
+
+            (
+                ReactdropState::Refunded,
+                format!(
+                    "The reactdrop could not be paid out, {} was returned to {}",
+                    reactdrop.tip_amount,
+                    pool::mention(reactdrop.author)
+                ),
+            )
+        } else {
+            (
+                ReactdropState::Failed,
+                format!(
+                    "The reactdrop of {} could not be paid out",
+                    pool::mention(reactdrop.author)
+                ),
+            )
+        };
+
+        if !database::update_reactdrop(
+            &mut tx,
+            reactdrop.channel_id.get() as i64,
+            reactdrop.message_id.get() as i64,
+            state,
+        )
+        .await?
+        {
+            // dropping the transaction rolls back the refund
+            warn!("reactdrop {} was cancelled while giving up", reactdrop.id);
+
+            return Ok(());
+        }
+
+        notifier::queue(
+            &mut tx,
+            Recipient::Channel(reactdrop.channel_id),
+            &CreateMessage::new().content(content),
+        )
+        .await?;
+
+        let admin_thread = ChannelId::new(
+            self.config
+                .application
+                .discord_admin_thread_id
+                .parse::<u64>()?,
+        );
+        notifier::queue(
+            &mut tx,
+            Recipient::Channel(admin_thread),
+            &CreateMessage::new()
+                .content(format!(
+                    ":warning: gave up on reactdrop #{} by {} ({}) after {attempts} attempts: {error}",
+                    reactdrop.id,
+                    pool::mention(reactdrop.author),
+                    reactdrop.tip_amount
+                ))
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        if let Err(e) = close_message(&self.http, reactdrop, "This reactdrop failed").await {
+            warn!("could not close reactdrop {}: {e:?}", reactdrop.id);
+        }
+
         Ok(())
     }
 }
 
+/// Draws at most `winners` users uniformly at random with a partial Fisher-Yates shuffle, which
+/// is simple enough for anyone to reproduce:
+///
+/// 1. sort the users by id, so the draw only depends on who participated and the seed
+/// 2. seed ChaCha20 with the seed, the bytes of the block hash
+/// 3. for every `i` in `0..winners`, take the next `u64` `r` and swap user `i` with user
+///    `i + (r * (n - i)) >> 64`, where `n` is the number of users
+/// 4. the first `winners` users win
+pub fn draw_winners(mut users: Vec<UserId>, winners: usize, seed: [u8; 32]) -> Vec<UserId> {
+    users.sort();
+
+    let mut rng = ChaCha20Rng::from_seed(seed);
+    let n = users.len();
+    let winners = winners.min(n);
+
+    for i in 0..winners {
+        let j = i + ((rng.next_u64() as u128 * (n - i) as u128) >> 64) as usize;
+        users.swap(i, j);
+    }
+
+    users.truncate(winners);
+    users
+}
+
+fn seed_from_block_hash(block_hash: &str) -> Option<[u8; 32]> {
+    if block_hash.len() != 64 {
+        return None;
+    }
+
+    let mut seed = [0u8; 32];
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(block_hash.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    Some(seed)
+}
+
 impl IntoSubsystem<Box<dyn std::error::Error + Send + Sync>> for Subsystem {
     async fn run(self, subsys: &mut SubsystemHandle) -> Result<(), Error> {
         while !subsys.is_shutdown_requested() {
-            if let Err(e) = self.check_running_reactdrops().await {
-                tracing::error!(?e, "Something went wrong when checking reactdrops");
-            }
+            let wait = match self.check_running_reactdrops().await {
+                Ok(wait) => wait,
+                Err(e) => {
+                    tracing::error!(?e, "Something went wrong when checking reactdrops");
+                    RETRY_DELAY
+                }
+            };
+            trace!("next reactdrop check in {wait:?}");
 
             tokio::select! {
-                _ = sleep(Duration::from_secs(10)) => {}
+                _ = sleep(wait) => {}
+                _ = self.wakeup.notified() => {
+                    trace!("reactdrop subsystem woken up");
+                }
                 _ = subsys.on_shutdown_requested() => {}
             }
         }
@@ -210,3 +903,139 @@ impl IntoSubsystem<Box<dyn std::error::Error + Send + Sync>> for Subsystem {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    const BLOCK_HASH: &str = "000000000004b7b3fa3b87a2e5e4ab7e0d48d0d4c4d6b70e2c8cfb0c3fa1f5e1";
+
+    #[test]
+    fn draw_is_reproducible() {
+        let seed = seed_from_block_hash(BLOCK_HASH).unwrap();
+        let users = (1..=20).map(UserId::new).collect::<Vec<_>>();
+        let mut reversed = users.clone();
+        reversed.reverse();
+
+        let winners = draw_winners(users.clone(), 3, seed);
+        assert_eq!(winners.len(), 3);
+        assert!(winners.iter().all(|winner| users.contains(winner)));
+        // the order in which participants were collected doesn't matter
+        assert_eq!(winners, draw_winners(reversed, 3, seed));
+    }
+
+    #[test]
+    fn draw_is_stable() {
+        // the published algorithm, so a change to it breaks this test
+        let seed = seed_from_block_hash(BLOCK_HASH).unwrap();
+        let users = (1..=20).map(UserId::new).collect::<Vec<_>>();
+
+        assert_eq!(
+            draw_winners(users, 3, seed),
+            [6, 17, 3].map(UserId::new).to_vec()
+        );
+    }
+
+    #[test]
+    fn committed_draw_height() {
+        // an hour is 60 blocks, plus a tenth and the margin
+        assert_eq!(draw_height(1000, 3600), 1000 + 60 + 6 + DRAW_MARGIN_BLOCKS);
+        assert_eq!(draw_height(1000, 30), 1000 + 1 + DRAW_MARGIN_BLOCKS);
+
+        assert_eq!(closing_draw_height(Some(1100), 1050), 1100);
+        // the committed block was mined before the entries were closed
+        assert_eq!(
+            closing_draw_height(Some(1100), 1100),
+            1100 + DRAW_MARGIN_BLOCKS
+        );
+        assert_eq!(closing_draw_height(None, 1100), 1100 + DRAW_MARGIN_BLOCKS);
+    }
+
+    #[test]
+    fn draw_more_winners_than_participants() {
+        let seed = seed_from_block_hash(BLOCK_HASH).unwrap();
+        let users = (1..=2).map(UserId::new).collect::<Vec<_>>();
+
+        assert_eq!(draw_winners(users, 5, seed).len(), 2);
+    }
+
+    #[test]
+    fn fewer_participants_than_winners_share_everything() {
+        let seed = seed_from_block_hash(BLOCK_HASH).unwrap();
+        let users = (1..=3).map(UserId::new).collect::<Vec<_>>();
+        let winners = draw_winners(users, 10, seed);
+        let shares =
+            commands::tipping::split_amount(Amount::from_sat(1_000), &winners, &Uuid::new_v4());
+
+        assert_eq!(shares.len(), 3);
+        assert_eq!(
+            shares.iter().map(|(_, share)| share.as_sat()).sum::<u64>(),
+            1_000
+        );
+    }
+
+    #[test]
+    fn state_roundtrip() {
+        for state in [
+            ReactdropState::Pending,
+            ReactdropState::Processed,
+            ReactdropState::Cancelled,
+            ReactdropState::Failed,
+            ReactdropState::Refunded,
+            ReactdropState::NoParticipants,
+        ] {
+            let parsed = ReactdropState::try_from(state.to_string()).unwrap();
+            assert_eq!(parsed.to_string(), state.to_string());
+        }
+
+        assert!(ReactdropState::try_from("exploded".to_string()).is_err());
+    }
+
+    #[test]
+    fn requirements() {
+        let now = chrono::Utc::now();
+        let days_ago = |days| now - chrono::Duration::days(days);
+        let role = RoleId::new(1);
+
+        let requirements = Requirements {
+            role: Some(role),
+            min_account_age_days: Some(30),
+            min_membership_days: Some(7),
+            max_participants: None,
+        };
+
+        assert!(requirements.is_met(days_ago(31), Some(days_ago(8)), &[role], now));
+        // too new an account
+        assert!(!requirements.is_met(days_ago(29), Some(days_ago(8)), &[role], now));
+        // joined too recently, or not a member
+        assert!(!requirements.is_met(days_ago(31), Some(days_ago(6)), &[role], now));
+        assert!(!requirements.is_met(days_ago(31), None, &[role], now));
+        // missing the role
+        assert!(!requirements.is_met(days_ago(31), Some(days_ago(8)), &[], now));
+
+        assert!(Requirements::default().is_met(now, None, &[], now));
+    }
+
+    #[test]
+    fn find_countdown() {
+        let content = format!(
+            ">>> **A reactdrop was started!**\n\n{}",
+            countdown(chrono::Utc::now())
+        );
+        assert_eq!(countdown_start(&content), content.find("Ends"));
+
+        let legacy =
+            ">>> **A reactdrop was started!**\n\nTime remaining: 1 hour(s) and 0 minute(s)";
+        assert_eq!(countdown_start(legacy), legacy.find("Time"));
+
+        assert!(countdown_start("something else").is_none());
+    }
+
+    #[test]
+    fn parse_block_hash() {
+        assert_eq!(seed_from_block_hash(BLOCK_HASH).unwrap()[6], 0xb7);
+        assert!(seed_from_block_hash("00ff").is_none());
+        assert!(seed_from_block_hash(&"zz".repeat(32)).is_none());
+    }
+}
@@ -0,0 +1,524 @@
+use std::{fmt::Display, str::FromStr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{
+    ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Http, Message, ReactionType, RoleId,
+    User, UserId,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use tokio::{sync::Notify, time::sleep};
+use tokio_graceful_shutdown::{IntoSubsystem, SubsystemHandle};
+use tracing::{debug, error, info, trace, warn};
+use vrsc::{Address, Amount};
+use vrsc_rpc::client::{Client as VerusClient, RpcApi};
+
+use crate::{
+    Error, VRSC_CURRENCY_ID,
+    commands::{self, tipping::Funding},
+    config::Config,
+    database,
+    notifier::{self, Recipient},
+    pool,
+    reactdrop::{self, Requirements},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+/// How many times a scheduled tip is tried before the amount goes back to its author.
+const MAX_ATTEMPTS: i32 = 5;
+/// The wait before the first retry, doubled after every failed attempt.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// What a scheduled tip does when it is due.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScheduledAction {
+    Reactdrop {
+        emoji: String,
+        duration_secs: i64,
+        winners: Option<u32>,
+        requirements: Requirements,
+    },
+    RoleTip {
+        role_id: RoleId,
+        note: Option<String>,
+        max_recipients: Option<usize>,
+    },
+}
+
+#[derive(Debug)]
+pub enum ScheduledTipStatus {
+    Pending,
+    /// Claimed by the scheduler, which is carrying it out.
+    Running,
+    Done,
+    /// Could not be carried out, the amount went back to the author.
+    Failed,
+    /// Cancelled before it was due, the amount went back to the author.
+    Cancelled,
+}
+
+impl Display for ScheduledTipStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pending => write!(f, "pending"),
+            Self::Running => write!(f, "running"),
+            Self::Done => write!(f, "done"),
+            Self::Failed => write!(f, "failed"),
+            Self::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ScheduledTip {
+    pub id: i64,
+    pub author: UserId,
+    /// The member that paid for the tip from a pool, the pool is the author then.
+    pub spender: Option<UserId>,
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub amount: Amount,
+    pub action: ScheduledAction,
+    pub run_at: DateTime<Utc>,
+}
+
+/// Takes the amount from the author's balance and stores the tip, to be carried out at `run_at`.
+/// Returns the id of the scheduled tip.
+#[allow(clippy::too_many_arguments)]
+pub async fn schedule(
+    conn: &mut PgConnection,
+    author: UserId,
+    spender: Option<UserId>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    amount: Amount,
+    action: &ScheduledAction,
+    run_at: DateTime<Utc>,
+) -> Result<i64, Error> {
+    let currency_id = Address::from_str(VRSC_CURRENCY_ID)?;
+
+    database::decrease_balance(&mut *conn, &author, &amount, &Amount::ZERO, &currency_id).await?;
+
+    database::insert_scheduled_tip(
+        conn,
+        author,
+        spender,
+        guild_id,
+        channel_id,
+        amount,
+        &currency_id,
+        &serde_json::to_string(action)?,
+        run_at,
+    )
+    .await
+}
+
+/// Carries out the action with an amount that was already taken from the author's balance, so
+/// the tipped amount or the reactdrop is funded from escrow.
+///
+/// A reactdrop is announced right away, so the announcement is returned: when the transaction
+/// that `conn` is part of fails after all, pass it to `discard`.
+#[allow(clippy::too_many_arguments)]
+pub async fn carry_out(
+    http: &Http,
+    verus: &VerusClient,
+    conn: &mut PgConnection,
+    author: UserId,
+    spender: Option<UserId>,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    amount: Amount,
+    action: &ScheduledAction,
+) -> Result<Option<Message>, Error> {
+    match action {
+        ScheduledAction::Reactdrop {
+            emoji,
+            duration_secs,
+            winners,
+            requirements,
+        } => {
+            let reaction_type = ReactionType::from_str(emoji)?;
+            let finish_time = chrono::Utc::now() + chrono::Duration::seconds(*duration_secs);
+            let draw_height = match winners {
+                Some(_) => Some(reactdrop::draw_height(
+                    verus.get_blockchain_info()?.blocks,
+                    *duration_secs,
+                )),
+                None => None,
+            };
+
+            let message = channel_id
+                .send_message(
+                    http,
+                    CreateMessage::new()
+                        .content(commands::reactdrop::announcement(
+                            author,
+                            amount,
+                            &reaction_type,
+                            *winners,
+                            draw_height,
+                            requirements,
+                            finish_time,
+                        ))
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await?;
+
+            let stored = async {
+                message.react(http, reaction_type.clone()).await?;
+
+                // the escrowed amount becomes the escrow of the reactdrop
+                database::insert_reactdrop(
+                    &mut *conn,
+                    author.get() as i64,
+                    spender.map(|spender| spender.get() as i64),
+                    reaction_type.to_string(),
+                    amount.as_sat() as i64,
+                    channel_id.get() as i64,
+                    message.id.get() as i64,
+                    finish_time,
+                    &Address::from_str(VRSC_CURRENCY_ID)?,
+                    Some(guild_id.get() as i64),
+                    *winners,
+                    draw_height,
+                    requirements,
+                )
+                .await
+            };
+
+            if let Err(e) = stored.await {
+                discard(http, Some(message)).await;
+
+                return Err(e);
+            }
+
+            return Ok(Some(message));
+        }
+        ScheduledAction::RoleTip {
+            role_id,
+            note,
+            max_recipients,
+        } => {
+            let members = role_members(http, guild_id, *role_id).await?;
+            let mut recipients =
+                commands::tipping::eligible_recipients(&mut *conn, author, spender, &members)
+                    .await?;
+
+            if let Some(max_recipients) = max_recipients {
+                recipients.sample(*max_recipients);
+            }
+
+            if recipients.users.is_empty() {
+                return Err("no members of the role can receive the tip".into());
+            }
+
+            commands::tipping::tip_multiple_users(
+                &mut *conn,
+                author,
+                &channel_id,
+                Some(guild_id),
+                recipients,
+                &amount,
+                "role",
+                note.as_deref(),
+                Funding::Escrow,
+            )
+            .await?;
+        }
+    }
+
+    Ok(None)
+}
+
+/// Deletes the announcement of a reactdrop that was not stored after all, so nobody reacts to a
+/// reactdrop that doesn't exist.
+pub async fn discard(http: &Http, announcement: Option<Message>) {
+    let Some(message) = announcement else {
+        return;
+    };
+
+    if let Err(e) = message.delete(http).await {
+        warn!(
+            "could not delete the announcement {} of a reactdrop that didn't start: {e:?}",
+            message.id
+        );
+    }
+}
+
+/// How long to wait before trying again after `attempts` failed attempts: `base`, doubled for
+/// every attempt after the first.
+pub fn backoff(base: Duration, attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+
+    base.saturating_mul(2u32.pow(doublings))
+}
+
+// the cache is not available here, so the members are fetched from Discord.
+async fn role_members(http: &Http, guild_id: GuildId, role_id: RoleId) -> Result<Vec<User>, Error> {
+    let mut members = vec![];
+    let mut after = None;
+
+    loop {
+        let page = guild_id.members(http, Some(1000), after).await?;
+        after = page.last().map(|member| member.user.id);
+
+        members.extend(
+            page.into_iter()
+                // the @everyone role has the same id as the guild
+                .filter(|member| member.roles.contains(&role_id) || role_id.get() == guild_id.get())
+                .map(|member| member.user),
+        );
+
+        if after.is_none() {
+            break;
+        }
+    }
+
+    trace!("{} members in role {role_id}", members.len());
+
+    Ok(members)
+}
+
+/// Carries out scheduled tips once they are due. Scheduled tips are stored in the database, so
+/// they survive restarts of the bot.
+pub struct Subsystem {
+    pub http: Arc<Http>,
+    pub pool: PgPool,
+    pub config: Config,
+    /// Wakes up the reactdrop subsystem when a scheduled reactdrop was started.
+    pub reactdrop_wakeup: Arc<Notify>,
+}
+
+impl Subsystem {
+    async fn run_due_tips(&self) -> Result<(), Error> {
+        let mut conn = self.pool.acquire().await?;
+        let due = database::get_due_scheduled_tips(&mut conn).await?;
+
+        for scheduled_tip in due {
+            debug!(
+                "running scheduled tip {}, due at {}",
+                scheduled_tip.id, scheduled_tip.run_at
+            );
+
+            // claimed outside of the transaction that carries it out, so a tip that was
+            // announced is never carried out again, not even when the bot stops halfway.
+            if !database::update_scheduled_tip(
+                &mut conn,
+                scheduled_tip.id,
+                ScheduledTipStatus::Pending,
+                ScheduledTipStatus::Running,
+                None,
+            )
+            .await?
+            {
+                debug!("scheduled tip {} was cancelled", scheduled_tip.id);
+
+                continue;
+            }
+
+            if let Err(e) = self.run(&scheduled_tip).await {
+                error!("scheduled tip {} failed: {e:?}", scheduled_tip.id);
+
+                if let Err(e) = self.record_failure(&scheduled_tip, e).await {
+                    error!(
+                        "could not record the failure of scheduled tip {}: {e:?}",
+                        scheduled_tip.id
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run(&self, scheduled_tip: &ScheduledTip) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let announcement = carry_out(
+            &self.http,
+            &self.config.application.verus_client()?,
+            &mut tx,
+            scheduled_tip.author,
+            scheduled_tip.spender,
+            scheduled_tip.guild_id,
+            scheduled_tip.channel_id,
+            scheduled_tip.amount,
+            &scheduled_tip.action,
+        )
+        .await?;
+
+        let finished = async {
+            if !database::update_scheduled_tip(
+                &mut tx,
+                scheduled_tip.id,
+                ScheduledTipStatus::Running,
+                ScheduledTipStatus::Done,
+                None,
+            )
+            .await?
+            {
+                return Err(
+                    format!("scheduled tip {} is not running anymore", scheduled_tip.id).into(),
+                );
+            }
+
+            tx.commit().await.map_err(Error::from)
+        };
+
+        if let Err(e) = finished.await {
+            discard(&self.http, announcement).await;
+
+            return Err(e);
+        }
+
+        info!("ran scheduled tip: {scheduled_tip:#?}");
+
+        if matches!(scheduled_tip.action, ScheduledAction::Reactdrop { .. }) {
+            self.reactdrop_wakeup.notify_one();
+        }
+
+        Ok(())
+    }
+
+    /// Counts a failed attempt and puts the tip back in the queue to be tried again later.
+    /// After `MAX_ATTEMPTS` the tip is given up on.
+    async fn record_failure(
+        &self,
+        scheduled_tip: &ScheduledTip,
+        error: Error,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let Some(attempts) =
+            database::record_scheduled_tip_failure(&mut tx, scheduled_tip.id, &error.to_string())
+                .await?
+        else {
+            return Ok(());
+        };
+
+        if attempts >= MAX_ATTEMPTS {
+            tx.commit().await?;
+
+            return self.fail(scheduled_tip, error).await;
+        }
+
+        let retry_at =
+            chrono::Utc::now() + chrono::Duration::from_std(backoff(RETRY_DELAY, attempts))?;
+        database::retry_scheduled_tip(&mut tx, scheduled_tip.id, retry_at).await?;
+
+        tx.commit().await?;
+
+        debug!(
+            "scheduled tip {} will be tried again at {retry_at}",
+            scheduled_tip.id
+        );
+
+        Ok(())
+    }
+
+    /// Gives the amount of a running scheduled tip that could not be carried out back to its
+    /// author.
+    async fn fail(&self, scheduled_tip: &ScheduledTip, error: Error) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if !database::update_scheduled_tip(
+            &mut tx,
+            scheduled_tip.id,
+            ScheduledTipStatus::Running,
+            ScheduledTipStatus::Failed,
+            Some(&error.to_string()),
+        )
+        .await?
+        {
+            return Ok(());
+        }
+
+        database::increase_balance(
+            &mut tx,
+            &scheduled_tip.author,
+            scheduled_tip.amount,
+            &Address::from_str(VRSC_CURRENCY_ID)?,
+        )
+        .await?;
+
+        notifier::queue(
+            &mut tx,
+            Recipient::Channel(scheduled_tip.channel_id),
+            &CreateMessage::new().content(format!(
+                "The scheduled tip #{} of {} could not be carried out, {} was returned",
+                scheduled_tip.id,
+                pool::mention(scheduled_tip.author),
+                scheduled_tip.amount
+            )),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Gives up on the tips that were being carried out when the bot stopped. Their transaction
+    /// never committed, so nothing was paid out, but a reactdrop may have been announced: trying
+    /// again could announce it twice.
+    async fn fail_interrupted_tips(&self) -> Result<(), Error> {
+        let interrupted = {
+            let mut conn = self.pool.acquire().await?;
+            database::get_running_scheduled_tips(&mut conn).await?
+        };
+
+        for scheduled_tip in interrupted {
+            warn!(
+                "scheduled tip {} was interrupted, giving it up",
+                scheduled_tip.id
+            );
+
+            self.fail(&scheduled_tip, "interrupted by a restart of the bot".into())
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl IntoSubsystem<Error> for Subsystem {
+    async fn run(self, subsys: &mut SubsystemHandle) -> Result<(), Error> {
+        if let Err(e) = self.fail_interrupted_tips().await {
+            error!(
+                ?e,
+                "Something went wrong when giving up on interrupted scheduled tips"
+            );
+        }
+
+        while !subsys.is_shutdown_requested() {
+            if let Err(e) = self.run_due_tips().await {
+                error!(?e, "Something went wrong when running scheduled tips");
+            }
+
+            tokio::select! {
+                _ = sleep(POLL_INTERVAL) => {}
+                _ = subsys.on_shutdown_requested() => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoffs() {
+        let base = Duration::from_secs(30);
+
+        assert_eq!(backoff(base, 1), base);
+        assert_eq!(backoff(base, 2), Duration::from_secs(60));
+        assert_eq!(backoff(base, 4), Duration::from_secs(240));
+        // no overflow, however many attempts there were
+        assert_eq!(backoff(base, i32::MAX), backoff(base, 17));
+        assert_eq!(backoff(base, 0), base);
+    }
+}
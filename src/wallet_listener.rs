@@ -1,7 +1,7 @@
 use anyhow::Context;
 use futures::StreamExt;
-use poise::serenity_prelude::{CreateMessage, Http, UserId};
-use sqlx::{PgConnection, PgPool};
+use poise::serenity_prelude::{CreateMessage, UserId};
+use sqlx::{Connection, PgConnection, PgPool};
 use std::collections::VecDeque;
 use std::str::FromStr;
 use std::sync::Arc;
@@ -16,8 +16,11 @@ use vrsc_rpc::{
     client::{Client, RpcApi},
 };
 
+use crate::commands::misc::NotificationEvent;
 use crate::config::Config;
 use crate::database::*;
+use crate::notifier::{self, Recipient};
+use crate::pool;
 use crate::{Error, VRSC_CURRENCY_ID};
 
 /// Listens for wallet transactions and processes them.
@@ -33,7 +36,6 @@ use crate::{Error, VRSC_CURRENCY_ID};
 /// txids in a database table. When maintenance mode is disabled, the transactions will be processed.
 #[derive(Debug)]
 pub struct TransactionProcessor {
-    http: Arc<Http>,
     pool: PgPool,
     config: Config,
     pub maintenance: Arc<RwLock<bool>>,
@@ -44,14 +46,12 @@ pub struct TransactionProcessor {
 
 impl TransactionProcessor {
     pub fn new(
-        http: Arc<Http>,
         pool: PgPool,
         config: Config,
         maintenance: Arc<RwLock<bool>>,
         deposits_enabled: Arc<RwLock<bool>>,
     ) -> Self {
         TransactionProcessor {
-            http,
             pool,
             config,
             maintenance,
@@ -204,7 +204,6 @@ impl TransactionProcessor {
         }
 
         let mut write = self.queue_small_txns.write().await;
-        let http = Arc::clone(&self.http);
         let queue_size = write.len();
         debug!("{queue_size} transactions in short queue");
 
@@ -234,7 +233,7 @@ impl TransactionProcessor {
                         break;
                     } else {
                         trace!("tx has at least {} confs: {}", min_confs, front.0);
-                        if let Err(e) = process_txid(Arc::clone(&http), &mut conn, &raw_tx).await {
+                        if let Err(e) = process_txid(&mut conn, &raw_tx).await {
                             error!(
                                 "something went wrong while handling a new wallet tx: {:?}\n{:?}",
                                 e, &front
@@ -266,7 +265,6 @@ impl TransactionProcessor {
             return Ok(());
         }
         let mut write = self.queue_large_txns.write().await;
-        let http = Arc::clone(&self.http);
         let queue_size = write.len();
         debug!("{queue_size} transactions in long queue");
 
@@ -295,7 +293,7 @@ impl TransactionProcessor {
                         break;
                     } else {
                         trace!("tx has at least {} confs: {}", min_confs, front.0);
-                        if let Err(e) = process_txid(Arc::clone(&http), &mut conn, &raw_tx).await {
+                        if let Err(e) = process_txid(&mut conn, &raw_tx).await {
                             error!(
                                 "something went wrong while handling a new wallet tx: {:?}\n{:?}",
                                 e, &front
@@ -322,9 +320,8 @@ impl TransactionProcessor {
 // checks if a transaction id contains an output address that belongs to a discord user
 // if it exists, the balance of that user is increased
 // the transactions is stored in the database such that it doesn't get processed again
-// a dm is sent to the user afterwards
+// and a dm to the user is put in the outbox, all in the same database transaction
 pub async fn process_txid(
-    http: Arc<Http>,
     conn: &mut PgConnection,
     raw_tx: &GetRawTransactionResultVerbose,
 ) -> Result<(), Error> {
@@ -334,8 +331,10 @@ pub async fn process_txid(
                 for address in addresses {
                     if let Some(user_id) = get_user_from_address(conn, address).await? {
                         let uuid = Uuid::new_v4();
+                        let mut tx = conn.begin().await?;
+
                         if let Err(e) = increase_balance(
-                            conn,
+                            &mut tx,
                             &user_id,
                             vout.value_sat,
                             &Address::from_str(VRSC_CURRENCY_ID)?,
@@ -347,7 +346,7 @@ pub async fn process_txid(
                                 &raw_tx.txid, vout.n, e
                             )
                         } else if let Err(e) = store_deposit_transaction(
-                            &mut *conn,
+                            &mut tx,
                             &uuid,
                             &user_id,
                             &raw_tx.txid,
@@ -361,8 +360,14 @@ pub async fn process_txid(
                                 "something went wrong while storing a transaction to the database: {:?}",
                                 e
                             )
+                        } else if let Err(e) = queue_deposit_dm(&mut tx, user_id, vout.value).await
+                        {
+                            error!(
+                                "something went wrong while queueing a deposit notification: {:?}",
+                                e
+                            )
                         } else {
-                            send_deposit_dm(http.clone(), user_id, vout.value).await?;
+                            tx.commit().await?;
                         }
                     }
                 }
@@ -377,13 +382,28 @@ pub async fn process_txid(
     Ok(())
 }
 
-async fn send_deposit_dm(http: Arc<Http>, user_id: UserId, amount: Amount) -> Result<(), Error> {
-    let user = http.get_user(user_id).await?;
-    user.direct_message(
-        http,
-        CreateMessage::new().content(format!("Your deposit of {amount} has been processed.")),
-    )
-    .await?;
+async fn queue_deposit_dm(
+    conn: &mut PgConnection,
+    user_id: UserId,
+    amount: Amount,
+) -> Result<(), Error> {
+    // nobody gets a DM for a deposit to a pool
+    if pool::pool_id(user_id).is_some() {
+        return Ok(());
+    }
 
-    Ok(())
+    if !get_notification_settings(&mut *conn, user_id)
+        .await?
+        .for_event(NotificationEvent::DepositConfirmed)
+        .wants_dm()
+    {
+        return Ok(());
+    }
+
+    notifier::queue(
+        conn,
+        Recipient::User(user_id),
+        &CreateMessage::new().content(format!("Your deposit of {amount} has been processed.")),
+    )
+    .await
 }